@@ -0,0 +1,68 @@
+//! Apply arbitrary insert sequences to a `SimpleHashMap`, and compare it with a std `HashMap`.
+//!
+//! The keys own heap memory, so AddressSanitizer catches uses of uninitialized or dropped entries,
+//! and LeakSanitizer catches entries that are never dropped. A hasher with very few distinct values
+//! makes collisions, hashes that map to the reserved bucket 0 and a full table common.
+
+#![no_main]
+
+use std::collections::HashMap;
+use std::hash::{BuildHasher, BuildHasherDefault, Hasher, RandomState};
+
+use arbitrary::Arbitrary;
+use frenzy::hashmap::SimpleHashMap;
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    capacity: u8,
+    safety_factor: u8,
+    few_bits: bool,
+    inserts: Vec<(Vec<u8>, i32)>,
+}
+
+/// A hash of at most 4 bits, the sum of the key bytes.
+#[derive(Default)]
+struct FewBitsHasher(u64);
+impl Hasher for FewBitsHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0 = bytes
+            .iter()
+            .fold(self.0, |acc, &b| acc.wrapping_add(b as u64));
+    }
+
+    fn finish(&self) -> u64 {
+        self.0 % 16
+    }
+}
+
+fn check<S: BuildHasher + Default>(input: &Input) {
+    let safety_factor = 1.0 + input.safety_factor as f32 / 64.0;
+    let mut map = SimpleHashMap::<Vec<u8>, i64, S>::new(input.capacity as usize, safety_factor);
+    let mut expected = HashMap::<Vec<u8>, i64>::new();
+    for (key, value) in &input.inserts {
+        *map.get_or_default(key.clone()) += *value as i64;
+        *expected.entry(key.clone()).or_default() += *value as i64;
+    }
+
+    let mut actual = HashMap::new();
+    for (key, value) in map.iter() {
+        assert!(
+            actual.insert(key.clone(), *value).is_none(),
+            "{key:?} twice"
+        );
+    }
+    assert_eq!(actual, expected);
+
+    let stats = map.stats();
+    assert_eq!(stats.occupied + stats.fallback_size, expected.len());
+    assert!(stats.occupied <= stats.capacity);
+}
+
+fuzz_target!(|input: Input| {
+    if input.few_bits {
+        check::<BuildHasherDefault<FewBitsHasher>>(&input);
+    } else {
+        check::<RandomState>(&input);
+    }
+});
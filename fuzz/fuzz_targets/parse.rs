@@ -0,0 +1,161 @@
+//! Feed arbitrary bytes through the fast engine, and compare its results with the reference
+//! implementation.
+//!
+//! The fast engine only accepts valid 1BRC input, so the bytes are first turned into a valid
+//! measurements file: each line keeps its station name (minus any separator and NUL, truncated to
+//! 100 bytes) and its temperature if valid, or gets one derived from its bytes, as are the other
+//! fields when the lines have several. Some files use other separators than `;` and `\n`, and some
+//! are CSV, with quoted names and comment lines. The file is then repeated until it is longer than
+//! the read ahead of the main loop, so both the main loop and the remainder handling see it. It ends
+//! exactly at the end of its allocation, so AddressSanitizer reports any read past EOF.
+
+#![no_main]
+
+use frenzy::cpu::CpuLevel;
+use frenzy::format::Separators;
+use frenzy::hash::HashKind;
+use frenzy::range::{MeasurementRange, OutOfRange};
+use frenzy::{BATCH_WIDTHS, Config, fast_field_results, reference};
+use libfuzzer_sys::fuzz_target;
+
+/// Longer than the read ahead of the main loop (128 bytes), for every worker.
+const MIN_LEN: usize = 1024;
+
+/// The measurements file, and the names of its stations.
+fn measurements(
+    data: &[u8],
+    fields: usize,
+    separators: Separators,
+    csv: bool,
+) -> (Vec<u8>, Vec<String>) {
+    let (delimiter, record) = (separators.delimiter as char, separators.record as char);
+    let mut lines = Vec::new();
+    let mut names = Vec::new();
+    for line in String::from_utf8_lossy(data).split('\n') {
+        let (name, temperature) = line.rsplit_once(';').unwrap_or((line, ""));
+        // NUL bytes are not supported in names, as the zero padding of short names would hide them,
+        // and CSV names keep their delimiters and quotes
+        let mut name = if csv {
+            name.replace([record, '\0'], "")
+        } else {
+            name.replace([';', delimiter, record, '\0'], "")
+        };
+        if name.is_empty() {
+            name.push('?');
+        }
+        while name.len() > 100 {
+            name.pop();
+        }
+        let hash = line
+            .bytes()
+            .fold(0i32, |acc, b| (acc * 31 + b as i32) % 1999);
+        let derived =
+            |field: i32| format!("{:.1}", ((hash + field * 337) % 1999 - 999) as f64 / 10.0);
+        names.push(name.clone());
+        if csv && (name.contains([delimiter, '"']) || name.starts_with('#') || hash % 4 == 0) {
+            name = format!("\"{}\"", name.replace('"', "\"\""));
+        }
+        let mut line = if is_valid_temperature(temperature) {
+            format!("{name}{delimiter}{temperature}")
+        } else {
+            format!("{name}{delimiter}{}", derived(0))
+        };
+        for field in 1..fields as i32 {
+            line.push(delimiter);
+            line.push_str(&derived(field));
+        }
+        line.push(record);
+        lines.push(line);
+    }
+
+    let file = lines.concat().into_bytes();
+    let repeat = MIN_LEN.div_ceil(file.len());
+    let mut padded = Vec::with_capacity(file.len() * repeat);
+    if csv {
+        padded.extend_from_slice(b"# header and comments\nstation,temperature\n#\n");
+    }
+    for _ in 0..repeat {
+        padded.extend_from_slice(&file);
+    }
+    (padded, names)
+}
+
+/// `-?\d?\d\.\d`
+fn is_valid_temperature(s: &str) -> bool {
+    let digits = s.strip_prefix('-').unwrap_or(s).as_bytes();
+    match digits {
+        [a, b'.', c] => a.is_ascii_digit() && c.is_ascii_digit(),
+        [a, b, b'.', c] => a.is_ascii_digit() && b.is_ascii_digit() && c.is_ascii_digit(),
+        _ => false,
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    // pick the configuration from the input, so it stays a plain measurements file and the seed
+    // corpus can be made of real samples
+    let selector = data.iter().fold(0usize, |acc, &b| {
+        acc.wrapping_mul(31).wrapping_add(b as usize)
+    });
+    let cpu_levels = CpuLevel::ALL
+        .into_iter()
+        .filter(|level| level.is_supported())
+        .collect::<Vec<_>>();
+    // a quarter of the runs have lines of 3 fields
+    let fields = if selector / 8192 % 4 == 3 { 3 } else { 1 };
+    // a quarter of the runs separate the fields with tabs and the lines with `|`, and another
+    // quarter are CSV
+    let (separators, csv) = match selector / 32768 % 4 {
+        2 => (Separators::new(b',', b'\n').unwrap(), true),
+        3 => (Separators::new(b'\t', b'|').unwrap(), false),
+        _ => (Separators::default(), false),
+    };
+    let (file, mut names) = measurements(data, fields, separators, csv);
+    // copy into an allocation of the exact size
+    let file = file.into_boxed_slice();
+    names.sort();
+    names.dedup();
+    // half of the runs only aggregate every other station, and skip the lines of the others
+    let stations =
+        (selector / 1024 % 2 == 1).then(|| names.iter().step_by(2).cloned().collect::<Vec<_>>());
+    // half of the runs drop or clamp the measurements out of a range
+    let range = (selector / 2048 % 2 == 1).then(|| MeasurementRange {
+        min: -50.0,
+        max: 50.0,
+        out_of_range: if selector / 4096 % 2 == 1 {
+            OutOfRange::Clamp
+        } else {
+            OutOfRange::Drop
+        },
+    });
+
+    let config = Config {
+        hash: HashKind::ALL[selector % HashKind::ALL.len()],
+        cpu: Some(cpu_levels[selector / 8 % cpu_levels.len()]),
+        workers: Some(1 + selector / 64 % 4),
+        batch: Some(BATCH_WIDTHS[selector / 256 % BATCH_WIDTHS.len()]),
+        stations,
+        range,
+        fields: Some(fields),
+        separators,
+        csv,
+        ..Config::default()
+    };
+    let fast = fast_field_results(&file, &config);
+
+    let summaries = reference::aggregate_fields(&file[..], &config);
+    assert_eq!(fast.len(), summaries.len());
+    for (fast, summaries) in fast.iter().zip(&summaries) {
+        let mut reference = reference::results(summaries);
+        if let Some(stations) = &config.stations {
+            reference.retain(|result| stations.iter().any(|name| *name == result.name));
+        }
+        let differences = reference::diff(fast, &reference);
+        for difference in &differences {
+            eprintln!(
+                "{}: fast {:?}, reference {:?}",
+                difference.name, difference.fast, difference.reference
+            );
+        }
+        assert!(differences.is_empty(), "{config:?}");
+    }
+});
@@ -0,0 +1,569 @@
+use std::path::PathBuf;
+
+use frenzy::cpu::CpuLevel;
+use frenzy::format::{Separators, parse_separator};
+use frenzy::generate;
+use frenzy::group::GroupKey;
+use frenzy::hash::HashKind;
+use frenzy::output::{NameFilter, OutputOptions, Selection, SortKey, SortOrder};
+use frenzy::range::{MeasurementRange, OutOfRange};
+use frenzy::{BATCH_WIDTHS, Config, MAX_FIELDS};
+
+use crate::bench;
+
+const USAGE: &str = "\
+usage: frenzy [options] <measurements file>
+       frenzy diff-check [options] <measurements file>
+       frenzy bench [options] [bench options] <measurements file>
+       frenzy generate [generate options]
+
+commands:
+    diff-check          run both the fast engine and the reference implementation, and report any
+                        station whose output differs
+    bench               time repeated runs of the whole pipeline, phase by phase, and compare the
+                        configurations given as comma separated lists to --workers, --batch and --hash
+    generate            write a random measurements file, deterministic by seed
+
+options:
+    --reference         use the straightforward reference implementation instead of the fast engine
+    --hash <xor|mulrot|fx|crc32|secure>
+                        hash function used for the station names (default: xor)
+    --secure-hash       same as `--hash secure`, a randomly seeded hash that resists crafted collisions.
+                        use it for untrusted input
+    --cpu <baseline|avx2|avx512>
+                        instruction set used by the parsing loop, each with its own byte search
+                        kernels (default: best supported by the CPU)
+    --workers <n>       number of worker threads (default: available parallelism)
+    --batch <1|2|4|8>   number of cursors each worker advances together in the main loop (default: 4)
+    --stats             print hash map diagnostics (capacity, occupancy, collisions) to stderr
+    --timings           print the wall and CPU time of each phase, and of each worker with the bytes
+                        and rows it parsed, to stderr
+    --progress          print the progress of the parsing (percent, throughput and ETA) to stderr
+    --sort <name|mean|min|max|count>
+                        order of the stations in the output (default: name)
+    --desc              sort in descending order, the stations with the same value staying in
+                        ascending order of their names
+    --collation <bytes|folded>
+                        how the names are compared: by their UTF-8 bytes, or without case and
+                        accents first, then without case, then by bytes (default: bytes)
+    --top <k>           only output the k stations with the highest values of --by, in descending
+                        order unless --sort or --desc is given
+    --bottom <k>        only output the k stations with the lowest values of --by, in ascending
+                        order unless --sort or --desc is given
+    --by <mean|min|max|count|name>
+                        value ranked by --top and --bottom (default: mean)
+    --stations <file>   only aggregate the stations listed in the file, one name per line. the lines
+                        of the other stations are skipped while parsing
+    --min <degrees>     drop the measurements below this value, counting them per station on stderr
+    --max <degrees>     drop the measurements above this value, counting them per station on stderr
+    --clamp             replace the measurements out of --min and --max by the closest bound instead
+                        of dropping them
+    --fields <k>        number of numeric fields after the station name, each summarized on its own
+                        output line, such as 3 for `<station>;<temp>;<humidity>;<pressure>`, at most
+                        16 (default: 1)
+    --delimiter <c>     character after the station name and each field but the last one, such as ,
+                        or \\t (default: ;)
+    --record-separator <c>
+                        character at the end of each line, including the last one (default: \\n)
+    --csv               read the file as CSV, with , as the default delimiter: skip the header line
+                        and the comment lines starting with #, and accept station names in double
+                        quotes, with \"\" for a quote inside them
+    --bucket <hour|day|month>
+                        read lines of `<station>;<timestamp>;<temperature>`, the timestamps in
+                        seconds since the Unix epoch or in ISO-8601, and output the time series of
+                        each station on a line, its results per bucket in chronological order
+    --prefix <prefix>   only output the stations whose name starts with the prefix
+    --match <regex>     only output the stations whose name matches the regular expression, anywhere
+                        in the name unless anchored with ^ and $
+    --group-by <station|sep:<c>:<n>|prefix:<n>|map:<file>>
+                        output the results merged per group of stations, on a line per --group-by
+                        in the order given: by the part of the name before its nth separator c, by
+                        its first n characters, or by the `<station>;<group>` lines of a file,
+                        leaving out the stations it doesn't list
+
+bench options:
+    --iterations <n>    timed runs of each configuration (default: 10)
+    --warmup <n>        untimed runs of each configuration before the timed ones (default: 1)
+    --json              print the report as JSON
+
+generate options:
+    -o <file>           output file (default: measurements.txt)
+    --rows <n>          number of rows (default: 1000000000)
+    --stations <n>      number of distinct stations, at most 10000 (default: 413)
+    --seed <n>          seed of the random generator (default: 0)
+    --name-len <real|min-max>
+                        station names from the 1BRC list, or random names with a length in bytes
+                        uniform in min..=max (default: real)
+    --malformed <ratio> fraction of the rows written as malformed lines, for testing the handling of
+                        invalid input (default: 0)";
+
+/// The options of the main loop and of the output of the stations, which the time series of
+/// `--bucket` don't use.
+const NOT_WITH_BUCKET: &[&str] = &[
+    "--stats",
+    "--timings",
+    "--progress",
+    "--sort",
+    "--desc",
+    "--collation",
+    "--top",
+    "--bottom",
+    "--by",
+    "--group-by",
+    "--fields",
+    "--csv",
+];
+
+pub(crate) enum Command {
+    Run,
+    DiffCheck,
+    Bench(bench::Options),
+    Generate(generate::Options),
+}
+impl Command {
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Run => "a run",
+            Command::DiffCheck => "diff-check",
+            Command::Bench(_) => "bench",
+            Command::Generate(_) => "generate",
+        }
+    }
+
+    /// The options valid for the command.
+    fn options(&self) -> &'static [&'static str] {
+        match self {
+            Command::Run => &[
+                "--hash",
+                "--secure-hash",
+                "--cpu",
+                "--workers",
+                "--batch",
+                "--reference",
+                "--stats",
+                "--timings",
+                "--progress",
+                "--sort",
+                "--desc",
+                "--collation",
+                "--top",
+                "--bottom",
+                "--by",
+                "--stations",
+                "--prefix",
+                "--match",
+                "--group-by",
+                "--min",
+                "--max",
+                "--clamp",
+                "--fields",
+                "--delimiter",
+                "--record-separator",
+                "--csv",
+                "--bucket",
+            ],
+            Command::DiffCheck => &[
+                "--hash",
+                "--secure-hash",
+                "--cpu",
+                "--workers",
+                "--batch",
+                "--stats",
+                "--stations",
+                "--min",
+                "--max",
+                "--clamp",
+                "--fields",
+                "--delimiter",
+                "--record-separator",
+                "--csv",
+                "--bucket",
+            ],
+            Command::Bench(_) => &[
+                "--hash",
+                "--secure-hash",
+                "--cpu",
+                "--workers",
+                "--batch",
+                "--iterations",
+                "--warmup",
+                "--json",
+                "--sort",
+                "--desc",
+                "--collation",
+                "--top",
+                "--bottom",
+                "--by",
+                "--stations",
+                "--prefix",
+                "--match",
+                "--group-by",
+                "--min",
+                "--max",
+                "--clamp",
+                "--fields",
+                "--delimiter",
+                "--record-separator",
+                "--csv",
+            ],
+            Command::Generate(_) => &[
+                "-o",
+                "--rows",
+                "--stations",
+                "--seed",
+                "--name-len",
+                "--malformed",
+            ],
+        }
+    }
+}
+
+pub(crate) struct Args {
+    pub command: Command,
+    /// The file to read, or to write for `generate`
+    pub measurements_file: PathBuf,
+    pub reference: bool,
+    /// Print the time of each phase to stderr
+    pub timings: bool,
+    /// Stations in the output and their order
+    pub output: OutputOptions,
+    pub config: Config,
+}
+impl Args {
+    pub fn parse() -> Self {
+        let mut args = std::env::args().skip(1);
+        let mut command = Command::Run;
+        let mut measurements_file = None;
+        let mut reference = false;
+        let mut timings = false;
+        let mut order = SortOrder::default();
+        let mut filter = NameFilter::default();
+        let mut group_by = Vec::new();
+        // a list file, or a number of stations for generate, known after the command
+        let mut stations = None;
+        let (mut min, mut max, mut out_of_range) = (None, None, OutOfRange::Drop);
+        let mut separators = Separators::default();
+        // `,` by default with --csv
+        let mut delimiter = None;
+        let mut order_given = false;
+        // --top or --bottom, and --by
+        let mut ranking: Option<(usize, bool)> = None;
+        let mut ranking_key = None;
+        let mut config = Config::default();
+        let mut options_seen = Vec::new();
+        // lists of values with bench, to compare the configurations
+        let (mut hashes, mut workers, mut batches) = (Vec::new(), Vec::new(), Vec::new());
+        let mut bench = bench::Options::default();
+        let mut output = PathBuf::from("measurements.txt");
+        let mut generate = generate::Options {
+            workers: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            ..generate::Options::default()
+        };
+        while let Some(arg) = args.next() {
+            if arg.starts_with('-') {
+                options_seen.push(arg.clone());
+            }
+            match arg.as_str() {
+                "--hash" => hashes.extend(parse_list::<HashKind>(&arg, args.next())),
+                "--secure-hash" => hashes.push(HashKind::Secure),
+                "--stats" => config.stats = true,
+                "--reference" => reference = true,
+                "--timings" => timings = true,
+                "--progress" => config.progress = true,
+                "--sort" => {
+                    order.key = parse_value(&arg, args.next());
+                    order_given = true;
+                }
+                "--desc" => {
+                    order.descending = true;
+                    order_given = true;
+                }
+                "--top" | "--bottom" => {
+                    if ranking.is_some() {
+                        usage_error("only one of --top and --bottom can be given");
+                    }
+                    ranking = Some((parse_value(&arg, args.next()), arg == "--top"));
+                }
+                "--by" => ranking_key = Some(parse_value::<SortKey>(&arg, args.next())),
+                "--collation" => order.collation = parse_value(&arg, args.next()),
+                "--stations" => stations = Some(parse_value::<String>(&arg, args.next())),
+                "--prefix" => filter.prefix = Some(parse_value(&arg, args.next())),
+                "--match" => filter.pattern = Some(parse_value(&arg, args.next())),
+                "--group-by" => group_by.push(parse_group_key(&arg, args.next())),
+                "--min" => min = Some(parse_value::<f64>(&arg, args.next())),
+                "--max" => max = Some(parse_value::<f64>(&arg, args.next())),
+                "--clamp" => out_of_range = OutOfRange::Clamp,
+                "--delimiter" => delimiter = Some(parse_separator_value(&arg, args.next())),
+                "--record-separator" => {
+                    separators.record = parse_separator_value(&arg, args.next())
+                }
+                "--csv" => config.csv = true,
+                "--bucket" => config.bucket = Some(parse_value(&arg, args.next())),
+                "--fields" => {
+                    let fields = parse_value(&arg, args.next());
+                    if !(1..=MAX_FIELDS).contains(&fields) {
+                        usage_error(&format!(
+                            "the number of fields must be within 1..={MAX_FIELDS}"
+                        ));
+                    }
+                    config.fields = Some(fields);
+                }
+                "--cpu" => {
+                    let level: CpuLevel = parse_value(&arg, args.next());
+                    if !level.is_supported() {
+                        usage_error(&format!("cpu level '{}' is not supported", level.name()));
+                    }
+                    config.cpu = Some(level);
+                }
+                "--workers" => {
+                    let list = parse_list::<usize>(&arg, args.next());
+                    if list.contains(&0) {
+                        usage_error("the number of workers must be at least 1");
+                    }
+                    workers.extend(list);
+                }
+                "--batch" => {
+                    let list = parse_list::<usize>(&arg, args.next());
+                    if let Some(batch) = list.iter().find(|b| !BATCH_WIDTHS.contains(b)) {
+                        usage_error(&format!(
+                            "unsupported batch width {batch}, expected one of {BATCH_WIDTHS:?}"
+                        ));
+                    }
+                    batches.extend(list);
+                }
+                "--iterations" => {
+                    bench.iterations = parse_value(&arg, args.next());
+                    if bench.iterations == 0 {
+                        usage_error("the number of iterations must be at least 1");
+                    }
+                }
+                "--warmup" => bench.warmup = parse_value(&arg, args.next()),
+                "--json" => bench.json = true,
+                "-o" => output = parse_value(&arg, args.next()),
+                "--rows" => generate.rows = parse_value(&arg, args.next()),
+                "--seed" => generate.seed = parse_value(&arg, args.next()),
+                "--name-len" => generate.names = parse_value(&arg, args.next()),
+                "--malformed" => {
+                    generate.malformed = parse_value(&arg, args.next());
+                    if !(0.0..=1.0).contains(&generate.malformed) {
+                        usage_error("the malformed ratio must be within 0..=1");
+                    }
+                }
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+                _ if arg.starts_with('-') => usage_error(&format!("unknown option '{arg}'")),
+                "diff-check" if measurements_file.is_none() => command = Command::DiffCheck,
+                "bench" if measurements_file.is_none() => {
+                    command = Command::Bench(bench::Options::default())
+                }
+                "generate" if measurements_file.is_none() => {
+                    command = Command::Generate(generate::Options::default())
+                }
+                _ if measurements_file.is_none() => measurements_file = Some(PathBuf::from(arg)),
+                _ => usage_error(&format!("unexpected argument '{arg}'")),
+            }
+        }
+        if let Some(option) = options_seen
+            .iter()
+            .find(|option| !command.options().contains(&option.as_str()))
+        {
+            usage_error(&format!("'{option}' is not valid with {}", command.name()));
+        }
+        if config.bucket.is_some()
+            && let Some(option) = options_seen
+                .iter()
+                .find(|option| NOT_WITH_BUCKET.contains(&option.as_str()))
+        {
+            usage_error(&format!("'{option}' is not valid with --bucket"));
+        }
+
+        let selection = ranking.map(|(count, top)| Selection {
+            count,
+            key: ranking_key.unwrap_or(SortKey::Mean),
+            top,
+        });
+        if selection.is_none() && ranking_key.is_some() {
+            usage_error("--by is only valid with --top or --bottom");
+        }
+        if let Some(selection) = selection
+            && !order_given
+        {
+            order = selection.order(order.collation);
+        }
+        if min.is_some() || max.is_some() {
+            let range = MeasurementRange {
+                min: min.unwrap_or(f64::NEG_INFINITY),
+                max: max.unwrap_or(f64::INFINITY),
+                out_of_range,
+            };
+            if range.min.is_nan() || range.max.is_nan() || range.min > range.max {
+                usage_error("--min must not be greater than --max");
+            }
+            config.range = Some(range);
+        } else if out_of_range == OutOfRange::Clamp {
+            usage_error("--clamp is only valid with --min or --max");
+        }
+        let delimiter = delimiter.unwrap_or(if config.csv {
+            b','
+        } else {
+            separators.delimiter
+        });
+        config.separators =
+            Separators::new(delimiter, separators.record).unwrap_or_else(|err| usage_error(&err));
+        if config.csv
+            && [delimiter, separators.record]
+                .iter()
+                .any(|b| b"\"#".contains(b))
+        {
+            usage_error("with --csv, the separators can't be '\"' or '#'");
+        }
+        let output_options = OutputOptions {
+            filter,
+            selection,
+            order,
+            group_by,
+        };
+        if let Some(stations) = stations {
+            if let Command::Generate(_) = command {
+                generate.stations = parse_value("--stations", Some(stations));
+                if !(1..=generate::MAX_STATIONS).contains(&generate.stations) {
+                    usage_error(&format!(
+                        "the number of stations must be within 1..={}",
+                        generate::MAX_STATIONS
+                    ));
+                }
+            } else {
+                config.stations = Some(read_station_list(&stations));
+            }
+        }
+
+        match &mut command {
+            Command::Run | Command::DiffCheck => {
+                if reference && (timings || config.progress) {
+                    usage_error("--timings and --progress are only supported by the fast engine");
+                }
+                if hashes.len() > 1 || workers.len() > 1 || batches.len() > 1 {
+                    usage_error("lists of configurations are only valid with bench");
+                }
+                config.hash = hashes.first().copied().unwrap_or_default();
+                config.workers = workers.first().copied();
+                config.batch = batches.first().copied();
+            }
+            Command::Bench(bench_options) => {
+                // all the combinations, None for the defaults
+                let hashes = if hashes.is_empty() {
+                    vec![HashKind::default()]
+                } else {
+                    hashes
+                };
+                let workers = list_or_default(workers);
+                let batches = list_or_default(batches);
+                bench.configs = Vec::new();
+                for &hash in &hashes {
+                    for &workers in &workers {
+                        for &batch in &batches {
+                            bench.configs.push(Config {
+                                hash,
+                                workers,
+                                batch,
+                                ..config.clone()
+                            });
+                        }
+                    }
+                }
+                bench.output = output_options.clone();
+                *bench_options = bench;
+            }
+            Command::Generate(generate_options) => {
+                if let Some(file) = measurements_file {
+                    usage_error(&format!("unexpected argument '{}'", file.display()));
+                }
+                *generate_options = generate;
+                measurements_file = Some(output);
+            }
+        }
+        Self {
+            command,
+            measurements_file: measurements_file
+                .unwrap_or_else(|| usage_error("Missing measurements file argument")),
+            reference,
+            timings,
+            output: output_options,
+            config,
+        }
+    }
+}
+
+/// The station names of a `--stations` file, one per line.
+fn read_station_list(path: &str) -> Vec<String> {
+    let list = std::fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("error: cannot read the station list '{path}': {err}");
+        std::process::exit(1)
+    });
+    list.lines()
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// A `--delimiter` or `--record-separator` value.
+fn parse_separator_value(option: &str, value: Option<String>) -> u8 {
+    let value: String = parse_value(option, value);
+    parse_separator(&value)
+        .unwrap_or_else(|err| usage_error(&format!("invalid value for '{option}': {err}")))
+}
+
+/// A `--group-by` value, reading the mapping file of `map:<file>`.
+fn parse_group_key(option: &str, value: Option<String>) -> GroupKey {
+    let value: String = parse_value(option, value);
+    let Some(path) = value.strip_prefix("map:") else {
+        return parse_value(option, Some(value));
+    };
+    let contents = std::fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("error: cannot read the group mapping '{path}': {err}");
+        std::process::exit(1)
+    });
+    GroupKey::mapping(&contents).unwrap_or_else(|err| {
+        eprintln!("error: invalid group mapping '{path}': {err}");
+        std::process::exit(1)
+    })
+}
+
+fn list_or_default(list: Vec<usize>) -> Vec<Option<usize>> {
+    if list.is_empty() {
+        vec![None]
+    } else {
+        list.into_iter().map(Some).collect()
+    }
+}
+
+/// A comma separated list of values.
+fn parse_list<T>(option: &str, value: Option<String>) -> Vec<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let value = value.unwrap_or_else(|| usage_error(&format!("missing value for '{option}'")));
+    value
+        .split(',')
+        .map(|item| parse_value(option, Some(item.to_string())))
+        .collect()
+}
+
+fn parse_value<T>(option: &str, value: Option<String>) -> T
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let value = value.unwrap_or_else(|| usage_error(&format!("missing value for '{option}'")));
+    value
+        .parse()
+        .unwrap_or_else(|err| usage_error(&format!("invalid value for '{option}': {err}")))
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("error: {msg}\n\n{USAGE}");
+    std::process::exit(2)
+}
@@ -0,0 +1,260 @@
+//! `frenzy bench`, repeated warm runs of the whole pipeline, timed phase by phase, for one or more
+//! configurations of the engine.
+
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use frenzy::cpu::CpuLevel;
+use frenzy::output::OutputOptions;
+use frenzy::timings::RunTimings;
+use frenzy::{Config, DEFAULT_BATCH};
+
+use crate::{RunOutput, map_file, run};
+
+pub(crate) struct Options {
+    /// Timed runs of each configuration
+    pub iterations: usize,
+    /// Untimed runs of each configuration before the timed ones, to warm the page cache
+    pub warmup: usize,
+    /// The configurations to compare, all the combinations of the options lists
+    pub configs: Vec<Config>,
+    /// Print the report as JSON instead of a table
+    pub json: bool,
+    /// Stations kept in the output, and their order, to time the sort phase with other orders
+    pub output: OutputOptions,
+}
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            iterations: 10,
+            warmup: 1,
+            configs: vec![Config::default()],
+            json: false,
+            output: OutputOptions::default(),
+        }
+    }
+}
+
+/// The wall time of a phase.
+type PhaseWall = fn(&RunTimings) -> Duration;
+
+/// The phases of a run, in order, with their wall time.
+const PHASES: [(&str, PhaseWall); 7] = [
+    ("mmap", |t| t.mmap.wall),
+    ("parse", |t| t.engine.parse.wall),
+    ("merge", |t| t.engine.merge.wall),
+    ("remainder", |t| t.engine.remainder.wall),
+    ("results", |t| t.engine.results.wall),
+    ("sort", |t| t.sort.wall),
+    ("output", |t| t.output.wall),
+];
+
+struct ConfigReport {
+    workers: usize,
+    batch: usize,
+    hash: &'static str,
+    cpu: &'static str,
+    runs: Vec<RunTimings>,
+}
+impl ConfigReport {
+    fn total(&self) -> Summary {
+        Summary::new(self.runs.iter().map(|run| run.total.wall).collect())
+    }
+
+    fn phase(&self, phase: usize) -> Summary {
+        let (_, wall) = PHASES[phase];
+        Summary::new(self.runs.iter().map(wall).collect())
+    }
+}
+
+struct Summary {
+    min: Duration,
+    median: Duration,
+    p95: Duration,
+}
+impl Summary {
+    fn new(mut durations: Vec<Duration>) -> Self {
+        durations.sort();
+        // nearest rank percentiles
+        let percentile =
+            |p: f64| durations[((p * durations.len() as f64).ceil() as usize).max(1) - 1];
+        Self {
+            min: durations[0],
+            median: percentile(0.5),
+            p95: percentile(0.95),
+        }
+    }
+}
+
+pub(crate) fn bench(measurements_file: &Path, options: &Options) {
+    assert!(options.iterations > 0);
+    let (bytes, rows) = {
+        let mmap = map_file(measurements_file);
+        // the separators are the same in every configuration
+        let record = options.configs[0].separators.record;
+        (mmap.len(), mmap.iter().filter(|&&b| b == record).count())
+    };
+
+    // every configuration must produce the same output
+    let mut expected_output = None;
+    let mut reports = Vec::new();
+    for config in &options.configs {
+        let mut runs = Vec::with_capacity(options.iterations);
+        for iteration in 0..options.warmup + options.iterations {
+            let RunOutput {
+                output,
+                timings: run,
+                ..
+            } = run(measurements_file, config, &options.output);
+            if *expected_output.get_or_insert_with(|| output.clone()) != output {
+                eprintln!("error: the output differs with {}", describe(config));
+                std::process::exit(1);
+            }
+            if iteration >= options.warmup {
+                runs.push(run);
+            }
+        }
+        reports.push(ConfigReport {
+            workers: config
+                .workers
+                .unwrap_or_else(|| std::thread::available_parallelism().unwrap().get()),
+            batch: config.batch.unwrap_or(DEFAULT_BATCH),
+            hash: config.hash.name(),
+            cpu: config.cpu.unwrap_or_else(CpuLevel::detect).name(),
+            runs,
+        });
+    }
+
+    let report = if options.json {
+        json_report(measurements_file, bytes, rows, &reports)
+    } else {
+        table_report(measurements_file, bytes, rows, options, &reports)
+    };
+    println!("{report}");
+}
+
+fn describe(config: &Config) -> String {
+    let mut desc = format!("hash {}", config.hash.name());
+    if let Some(workers) = config.workers {
+        write!(desc, ", {workers} workers").unwrap();
+    }
+    if let Some(batch) = config.batch {
+        write!(desc, ", batch {batch}").unwrap();
+    }
+    desc
+}
+
+fn table_report(
+    measurements_file: &Path,
+    bytes: usize,
+    rows: usize,
+    options: &Options,
+    reports: &[ConfigReport],
+) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{}: {bytes} bytes, {rows} rows, {} iterations after {} warmup",
+        measurements_file.display(),
+        options.iterations,
+        options.warmup
+    )
+    .unwrap();
+    writeln!(out, "times in ms, phases are medians\n").unwrap();
+
+    write!(
+        out,
+        "{:>7} {:>5} {:>6} {:>8} | {:>8} {:>8} {:>8} | {:>8} {:>6} |",
+        "workers", "batch", "hash", "cpu", "min", "median", "p95", "Mrows/s", "GB/s"
+    )
+    .unwrap();
+    for (phase, _) in PHASES {
+        write!(out, " {phase:>9}").unwrap();
+    }
+    for report in reports {
+        let total = report.total();
+        let secs = total.median.as_secs_f64();
+        write!(
+            out,
+            "\n{:>7} {:>5} {:>6} {:>8} | {:>8.1} {:>8.1} {:>8.1} | {:>8.1} {:>6.2} |",
+            report.workers,
+            report.batch,
+            report.hash,
+            report.cpu,
+            millis(total.min),
+            millis(total.median),
+            millis(total.p95),
+            rows as f64 / secs / 1e6,
+            bytes as f64 / secs / 1e9,
+        )
+        .unwrap();
+        for phase in 0..PHASES.len() {
+            write!(out, " {:>9.2}", millis(report.phase(phase).median)).unwrap();
+        }
+    }
+    out
+}
+
+fn json_report(
+    measurements_file: &Path,
+    bytes: usize,
+    rows: usize,
+    reports: &[ConfigReport],
+) -> String {
+    let summary_json = |summary: Summary| {
+        format!(
+            r#"{{"min": {}, "median": {}, "p95": {}}}"#,
+            summary.min.as_secs_f64(),
+            summary.median.as_secs_f64(),
+            summary.p95.as_secs_f64()
+        )
+    };
+    let configs = reports
+        .iter()
+        .map(|report| {
+            let total = report.total();
+            let secs = total.median.as_secs_f64();
+            let phases = PHASES
+                .iter()
+                .enumerate()
+                .map(|(i, (phase, _))| format!(r#""{phase}": {}"#, summary_json(report.phase(i))))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                r#"    {{"workers": {}, "batch": {}, "hash": "{}", "cpu": "{}", "seconds": {}, "rows_per_sec": {}, "bytes_per_sec": {}, "phases": {{{phases}}}}}"#,
+                report.workers,
+                report.batch,
+                report.hash,
+                report.cpu,
+                summary_json(total),
+                rows as f64 / secs,
+                bytes as f64 / secs,
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+    format!(
+        "{{\n  \"file\": {},\n  \"bytes\": {bytes},\n  \"rows\": {rows},\n  \"iterations\": {},\n  \"configs\": [\n{configs}\n  ]\n}}",
+        json_string(&measurements_file.to_string_lossy()),
+        reports.first().map_or(0, |report| report.runs.len()),
+    )
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e3
+}
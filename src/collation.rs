@@ -0,0 +1,59 @@
+//! Case and accent insensitive ordering of the station names, for `--collation folded`.
+//!
+//! A small subset of the Unicode collation algorithm, with no tables beyond the Latin letters: the
+//! names are compared first without case and accents, then without case, then by their bytes, so
+//! `Abéché` sorts between `Abbottabad` and `Abha`, and `İzmir` next to `Istanbul`.
+
+/// The primary and secondary collation keys of `name`.
+///
+/// The primary key is lowercase with the accents removed, the secondary one only lowercase. Two
+/// different names can have the same keys, such as `Paris` and `paris`: compare their bytes last.
+pub(crate) fn collation_key(name: &str) -> (String, String) {
+    let lowercase = name
+        .chars()
+        .flat_map(char::to_lowercase)
+        .collect::<String>();
+    let mut primary = String::with_capacity(lowercase.len());
+    for c in lowercase.chars() {
+        match base_letters(c) {
+            Some(base) => primary.push_str(base),
+            // decomposed accents, such as the dot of `İ` lowercased to `i\u{307}`
+            None if ('\u{300}'..='\u{36f}').contains(&c) => {}
+            None => primary.push(c),
+        }
+    }
+    (primary, lowercase)
+}
+
+/// The letters without accents of a lowercase letter with accents, or one written as several
+/// letters, such as `ß`.
+fn base_letters(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        // `ı`, the dotless i of Turkish
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĳ' => "ij",
+        'ĵ' => "j",
+        'ķ' | 'ĸ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' | 'ŉ' | 'ŋ' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' | 'ơ' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ș' | 'ſ' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' | 'ț' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' | 'ư' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    })
+}
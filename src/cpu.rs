@@ -0,0 +1,106 @@
+/// The instruction set variants the hot parsing loop is compiled for.
+///
+/// The variant is picked at startup with [`CpuLevel::detect`], so a single portable binary runs with
+/// the widest SIMD instructions available on the machine, without building with `-Ctarget-cpu=native`.
+/// Each level has its own byte search kernels, used by the whole main loop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CpuLevel {
+    /// Whatever the binary was compiled for, SSE2 on a default x86_64 build
+    Baseline,
+    /// AVX2 + BMI1/2 + LZCNT + POPCNT
+    Avx2,
+    /// AVX2 level + AVX-512 F/BW/VL
+    Avx512,
+}
+impl CpuLevel {
+    pub const ALL: [CpuLevel; 3] = [CpuLevel::Baseline, CpuLevel::Avx2, CpuLevel::Avx512];
+
+    /// The best level supported by the current CPU.
+    pub fn detect() -> Self {
+        Self::ALL
+            .into_iter()
+            .rev()
+            .find(|level| level.is_supported())
+            .unwrap()
+    }
+
+    pub fn is_supported(self) -> bool {
+        #[cfg(target_arch = "x86_64")]
+        {
+            let avx2 = || {
+                std::is_x86_feature_detected!("avx2")
+                    && std::is_x86_feature_detected!("bmi1")
+                    && std::is_x86_feature_detected!("bmi2")
+                    && std::is_x86_feature_detected!("lzcnt")
+                    && std::is_x86_feature_detected!("popcnt")
+            };
+            match self {
+                CpuLevel::Baseline => true,
+                CpuLevel::Avx2 => avx2(),
+                CpuLevel::Avx512 => {
+                    avx2()
+                        && std::is_x86_feature_detected!("avx512f")
+                        && std::is_x86_feature_detected!("avx512bw")
+                        && std::is_x86_feature_detected!("avx512vl")
+                }
+            }
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        {
+            self == CpuLevel::Baseline
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CpuLevel::Baseline => "baseline",
+            CpuLevel::Avx2 => "avx2",
+            CpuLevel::Avx512 => "avx512",
+        }
+    }
+}
+impl std::str::FromStr for CpuLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|level| level.name() == s)
+            .ok_or_else(|| format!("unknown cpu level '{s}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::XorHash;
+    use crate::test_utils::{measurements, naive_summaries, parsed_part, rows, summaries};
+
+    #[test]
+    fn all_levels_agree() {
+        let input = measurements(rows(20_000));
+        let parsed = parsed_part(&input);
+
+        let baseline = summaries(&crate::parse_file_bytes::<XorHash, crate::StationSummary>(
+            input.as_bytes(),
+            parsed,
+            crate::ParseOptions::new(CpuLevel::Baseline, crate::DEFAULT_BATCH),
+            None,
+        ));
+        assert_eq!(baseline, naive_summaries(parsed));
+        for level in CpuLevel::ALL
+            .into_iter()
+            .filter(|level| level.is_supported())
+        {
+            for batch in crate::BATCH_WIDTHS {
+                let map = crate::parse_file_bytes::<XorHash, crate::StationSummary>(
+                    input.as_bytes(),
+                    parsed,
+                    crate::ParseOptions::new(level, batch),
+                    None,
+                );
+                assert_eq!(summaries(&map), baseline, "{level:?} batch {batch}");
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! The layout of the input lines, `<name>;<temperature>\n` in the 1BRC format.
+
+use std::borrow::Cow;
+
+/// The bytes that end the name and the fields of a line, and the line itself: `--delimiter` and
+/// `--record-separator`.
+///
+/// The parsing kernels compare the input with these bytes instead of constants, so any of them is
+/// as fast as the 1BRC ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Separators {
+    /// After the name and every field but the last one, `;` by default
+    pub delimiter: u8,
+    /// After the last field of every line, including the last line, `\n` by default
+    pub record: u8,
+}
+impl Default for Separators {
+    fn default() -> Self {
+        Self {
+            delimiter: b';',
+            record: b'\n',
+        }
+    }
+}
+impl Separators {
+    /// Both separators must be ASCII, and can't appear in a measurement (`-`, `.` and the digits)
+    /// nor be the same byte.
+    pub fn new(delimiter: u8, record: u8) -> Result<Self, String> {
+        for byte in [delimiter, record] {
+            if !byte.is_ascii() || byte == 0 || byte.is_ascii_digit() || b"-.".contains(&byte) {
+                return Err(format!(
+                    "invalid separator {:?}, expected an ASCII character other than a digit, '-' or '.'",
+                    byte as char
+                ));
+            }
+        }
+        if delimiter == record {
+            return Err(format!(
+                "the delimiter and the record separator are both {:?}",
+                delimiter as char
+            ));
+        }
+        Ok(Self { delimiter, record })
+    }
+}
+
+/// A separator given on the command line: a single ASCII character, or one of the escapes `\t`,
+/// `\n` and `\r`.
+pub fn parse_separator(s: &str) -> Result<u8, String> {
+    match s {
+        "\\t" => Ok(b'\t'),
+        "\\n" => Ok(b'\n'),
+        "\\r" => Ok(b'\r'),
+        _ if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+        _ => Err(format!(
+            "expected a single ASCII character or one of \\t, \\n and \\r, got '{s}'"
+        )),
+    }
+}
+
+/// Whether a line of a `--csv` file starting with this byte needs the slow path of the main loop:
+/// a comment (`#`), or a quoted name. The other lines are parsed as usual.
+#[inline(always)]
+pub(crate) fn is_csv_special(first_byte: u8) -> bool {
+    first_byte == b'"' || first_byte == b'#'
+}
+
+/// The length of the preamble of a `--csv` file: its leading comment lines, and its header line if
+/// any. The first line that isn't a comment is a header if its last field isn't a measurement.
+pub(crate) fn csv_preamble_len(file: &[u8], separators: Separators) -> usize {
+    let mut len = 0;
+    for line in file.split_inclusive(|&b| b == separators.record) {
+        if line.starts_with(b"#") {
+            len += line.len();
+            continue;
+        }
+        let fields = line.strip_suffix(&[separators.record]).unwrap_or(line);
+        let last_field = fields
+            .rsplit(|&b| b == separators.delimiter)
+            .next()
+            .unwrap();
+        if !is_measurement(last_field) {
+            len += line.len();
+        }
+        break;
+    }
+    len
+}
+
+/// `-?\d?\d\.\d`
+pub(crate) fn is_measurement(bytes: &[u8]) -> bool {
+    let digits = bytes.strip_prefix(b"-").unwrap_or(bytes);
+    match digits {
+        [a, b'.', c] => a.is_ascii_digit() && c.is_ascii_digit(),
+        [a, b, b'.', c] => a.is_ascii_digit() && b.is_ascii_digit() && c.is_ascii_digit(),
+        _ => false,
+    }
+}
+
+/// Split a line starting with a quoted name (RFC 4180) into the bytes between the quotes, still
+/// escaped, whether they contain escaped quotes (`""`), and the fields after the delimiter that
+/// follows the name.
+///
+/// Panics if the closing quote is missing or not followed by the delimiter.
+pub(crate) fn split_quoted(line: &[u8], delimiter: u8) -> (&[u8], bool, &[u8]) {
+    debug_assert_eq!(line[0], b'"');
+    let mut escaped = false;
+    let mut i = 1;
+    loop {
+        let quote = i + line[i..]
+            .iter()
+            .position(|&b| b == b'"')
+            .expect("unterminated quoted name");
+        if line.get(quote + 1) == Some(&b'"') {
+            escaped = true;
+            i = quote + 2;
+            continue;
+        }
+        assert_eq!(
+            line.get(quote + 1),
+            Some(&delimiter),
+            "expected the delimiter after a quoted name"
+        );
+        return (&line[1..quote], escaped, &line[quote + 2..]);
+    }
+}
+
+/// A quoted name with its escaped quotes (`""`) replaced by single ones, borrowed if it has none.
+pub fn unescape(name: &str) -> Cow<'_, str> {
+    if name.contains("\"\"") {
+        Cow::Owned(name.replace("\"\"", "\""))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// The name of a station of a `--csv` file from its key in the map. The quoted names with escaped
+/// quotes are keyed by their bytes from the opening quote, still escaped, as no unquoted name starts
+/// with a quote. The other names are keyed by themselves.
+pub(crate) fn csv_name(key: &str) -> Cow<'_, str> {
+    match key.strip_prefix('"') {
+        Some(escaped) => unescape(escaped),
+        None => Cow::Borrowed(key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separators() {
+        assert_eq!(parse_separator(","), Ok(b','));
+        assert_eq!(parse_separator("\\t"), Ok(b'\t'));
+        assert_eq!(parse_separator("\t"), Ok(b'\t'));
+        assert!(parse_separator("ab").is_err());
+        assert!(parse_separator("é").is_err());
+
+        assert_eq!(Separators::new(b';', b'\n'), Ok(Separators::default()));
+        assert!(Separators::new(b'|', b'\r').is_ok());
+        assert!(Separators::new(b',', b',').is_err());
+        assert!(Separators::new(b'.', b'\n').is_err());
+        assert!(Separators::new(b';', b'7').is_err());
+        assert!(Separators::new(0, b'\n').is_err());
+    }
+
+    #[test]
+    fn csv() {
+        let separators = Separators::new(b',', b'\n').unwrap();
+        let preamble = |file: &str| csv_preamble_len(file.as_bytes(), separators);
+        assert_eq!(preamble("Bern,12.0\n"), 0);
+        assert_eq!(preamble("station,temperature\nBern,12.0\n"), 20);
+        assert_eq!(preamble("# export\n#\nstation,t\nBern,-2.5\n"), 21);
+        assert_eq!(preamble("# export\nBern,12.0\n"), 9);
+        assert_eq!(preamble("\"station\",\"temperature\"\n"), 24);
+        assert_eq!(preamble("station"), 7);
+
+        assert_eq!(
+            split_quoted(b"\"Washington, D.C.\",12.0", b','),
+            (&b"Washington, D.C."[..], false, &b"12.0"[..])
+        );
+        assert_eq!(
+            split_quoted(br#""The ""Pier"", Brighton",1.0,2.0"#, b','),
+            (&br#"The ""Pier"", Brighton"#[..], true, &b"1.0,2.0"[..])
+        );
+        assert_eq!(
+            split_quoted(br#""""",1.0"#, b','),
+            (&br#""""#[..], true, &b"1.0"[..])
+        );
+        assert_eq!(unescape(r#"The ""Pier"""#), r#"The "Pier""#);
+        assert!(matches!(unescape("Bern"), Cow::Borrowed("Bern")));
+        assert_eq!(csv_name(r#""The ""Pier"""#), r#"The "Pier""#);
+        assert_eq!(csv_name(r#"a""b"#), r#"a""b"#);
+    }
+}
@@ -0,0 +1,437 @@
+//! Generator of 1BRC measurements files, equivalent to the official `create_measurements.sh`.
+//!
+//! Each row picks one of the stations uniformly, and draws its temperature from a Gaussian around
+//! the station mean with a standard deviation of 10, like the official generator, clamped to
+//! `[-99.9, 99.9]`. The stations and their means come from the 1BRC station list, with numbered
+//! copies of the list when more than its 413 stations are requested.
+//!
+//! Rows are generated in parallel in fixed size chunks, each with a PRNG seeded by the seed and the
+//! chunk index, so the output only depends on the options, not on the number of threads.
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use crate::rng::Rng;
+
+/// The maximum number of stations allowed by the 1BRC spec.
+pub const MAX_STATIONS: usize = 10_000;
+
+const CHUNK_ROWS: u64 = 1 << 20;
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub rows: u64,
+    /// Number of distinct stations, at most [`MAX_STATIONS`]
+    pub stations: usize,
+    pub seed: u64,
+    pub names: NameLengths,
+    /// Fraction of the rows written as malformed lines, see [`Malformed`]
+    pub malformed: f64,
+    /// Number of threads
+    pub workers: usize,
+}
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            rows: 1_000_000_000,
+            stations: 413,
+            seed: 0,
+            names: NameLengths::Real,
+            malformed: 0.0,
+            workers: 1,
+        }
+    }
+}
+
+/// Distribution of the station name lengths.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NameLengths {
+    /// The names of the 1BRC station list
+    Real,
+    /// Random names, mixing ASCII and multi-byte UTF-8 characters, with a length in bytes uniform in
+    /// `min..=max`
+    Uniform { min: usize, max: usize },
+}
+impl std::str::FromStr for NameLengths {
+    type Err = String;
+
+    /// `real`, `<min>-<max>` or `<len>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "real" {
+            return Ok(NameLengths::Real);
+        }
+        let (min, max) = s.split_once('-').unwrap_or((s, s));
+        let parse = |len: &str| {
+            len.parse::<usize>()
+                .map_err(|_| format!("invalid name length '{len}'"))
+        };
+        let (min, max) = (parse(min)?, parse(max)?);
+        if !(1 <= min && min <= max && max <= 100) {
+            return Err(format!(
+                "name lengths must be within 1..=100 bytes, got {min}-{max}"
+            ));
+        }
+        Ok(NameLengths::Uniform { min, max })
+    }
+}
+
+/// The kinds of malformed lines, for testing the handling of invalid input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Malformed {
+    /// `Abha18.0`
+    MissingSemicolon,
+    /// `;18.0`
+    EmptyName,
+    /// `Abha;`
+    MissingTemperature,
+    /// `Abha;18`
+    NoDecimal,
+    /// `Abha;18.05`
+    TwoDecimals,
+    /// `Abha;123.4`
+    OutOfRange,
+    /// A name longer than 100 bytes
+    LongName,
+    /// `Abha;18.0\r`, a CRLF line ending
+    CarriageReturn,
+    /// An empty line
+    EmptyLine,
+    /// `Abha;18.0;1`
+    ExtraField,
+}
+impl Malformed {
+    pub const ALL: [Malformed; 10] = [
+        Malformed::MissingSemicolon,
+        Malformed::EmptyName,
+        Malformed::MissingTemperature,
+        Malformed::NoDecimal,
+        Malformed::TwoDecimals,
+        Malformed::OutOfRange,
+        Malformed::LongName,
+        Malformed::CarriageReturn,
+        Malformed::EmptyLine,
+        Malformed::ExtraField,
+    ];
+}
+
+struct Station {
+    name: String,
+    /// Mean temperature in degrees
+    mean: f64,
+}
+
+/// Write a measurements file with the given options to `out`.
+pub fn generate(options: &Options, out: impl Write) -> io::Result<()> {
+    generate_with_chunk_rows(options, out, CHUNK_ROWS)
+}
+
+fn generate_with_chunk_rows(
+    options: &Options,
+    mut out: impl Write,
+    chunk_rows: u64,
+) -> io::Result<()> {
+    assert!((0.0..=1.0).contains(&options.malformed));
+    let stations = stations(options)?;
+
+    let chunks_num = options.rows.div_ceil(chunk_rows);
+    let workers = options.workers.max(1) as u64;
+    // each round generates one chunk per worker, and writes them in order
+    for round_start in (0..chunks_num).step_by(workers as usize) {
+        let round = round_start..chunks_num.min(round_start + workers);
+        let buffers = std::thread::scope(|scope| {
+            let workers = round
+                .map(|chunk| {
+                    let rows = chunk_rows.min(options.rows - chunk * chunk_rows);
+                    let stations = &stations;
+                    scope.spawn(move || generate_chunk(options, stations, chunk, rows))
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .map(|w| w.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        for buffer in buffers {
+            out.write_all(&buffer)?;
+        }
+    }
+    out.flush()
+}
+
+fn stations(options: &Options) -> io::Result<Vec<Station>> {
+    if !(1..=MAX_STATIONS).contains(&options.stations) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("the number of stations must be within 1..={MAX_STATIONS}"),
+        ));
+    }
+    let mut rng = Rng::with_stream(options.seed, u64::MAX);
+
+    let mut list = include_str!("../data/weather_stations.csv")
+        .lines()
+        .map(|line| {
+            let (name, mean) = line.rsplit_once(';').unwrap();
+            (name, mean.parse::<f64>().unwrap())
+        })
+        .collect::<Vec<_>>();
+    // shuffle, so fewer stations than the list are a random subset of it
+    for i in (1..list.len()).rev() {
+        list.swap(i, rng.range(0..=i));
+    }
+
+    let mut names = HashSet::new();
+    let mut stations = Vec::with_capacity(options.stations);
+    for i in 0..options.stations {
+        let (list_name, mean) = list[i % list.len()];
+        let name = match options.names {
+            NameLengths::Real if i < list.len() => list_name.to_string(),
+            NameLengths::Real => format!("{list_name} {}", i / list.len() + 1),
+            NameLengths::Uniform { min, max } => {
+                let mut attempts = 0;
+                loop {
+                    let len = rng.range(min..=max);
+                    let name = random_name(&mut rng, len);
+                    if !names.contains(&name) {
+                        break name;
+                    }
+                    attempts += 1;
+                    if attempts == 1000 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("not enough distinct names of {min}-{max} bytes"),
+                        ));
+                    }
+                }
+            }
+        };
+        names.insert(name.clone());
+        stations.push(Station { name, mean });
+    }
+    Ok(stations)
+}
+
+/// Random name of exactly `len` bytes.
+fn random_name(rng: &mut Rng, len: usize) -> String {
+    const CHARS: &[char] = &[
+        'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r',
+        's', 't', 'u', 'v', 'w', 'x', 'y', 'z', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J',
+        'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', ' ', '-',
+        '.', '\'', 'é', 'ß', 'İ', '€', '東', '𝄞',
+    ];
+    let mut name = String::with_capacity(len);
+    while name.len() < len {
+        let c = *rng.choose(CHARS);
+        if name.len() + c.len_utf8() <= len {
+            name.push(c);
+        }
+    }
+    name
+}
+
+fn generate_chunk(options: &Options, stations: &[Station], chunk: u64, rows: u64) -> Vec<u8> {
+    let mut rng = Rng::with_stream(options.seed, chunk);
+    let mut buf = Vec::with_capacity(rows as usize * 16);
+    for _ in 0..rows {
+        let station = rng.choose(stations);
+        let value = ((station.mean + 10.0 * rng.gaussian()) * 10.0).round() as i32;
+        let value = value.clamp(-999, 999);
+        if options.malformed > 0.0 && rng.next_f64() < options.malformed {
+            write_malformed(&mut buf, *rng.choose(&Malformed::ALL), &station.name, value);
+        } else {
+            buf.extend_from_slice(station.name.as_bytes());
+            buf.push(b';');
+            write_temperature(&mut buf, value);
+            buf.push(b'\n');
+        }
+    }
+    buf
+}
+
+/// Write `value` tenths as a 1BRC temperature.
+fn write_temperature(buf: &mut Vec<u8>, value: i32) {
+    if value < 0 {
+        buf.push(b'-');
+    }
+    let value = value.unsigned_abs();
+    if value >= 100 {
+        buf.push(b'0' + (value / 100) as u8);
+    }
+    buf.extend_from_slice(&[
+        b'0' + (value / 10 % 10) as u8,
+        b'.',
+        b'0' + (value % 10) as u8,
+    ]);
+}
+
+fn write_malformed(buf: &mut Vec<u8>, kind: Malformed, name: &str, value: i32) {
+    let name = name.as_bytes();
+    match kind {
+        Malformed::MissingSemicolon => {
+            buf.extend_from_slice(name);
+            write_temperature(buf, value);
+        }
+        Malformed::EmptyName => {
+            buf.push(b';');
+            write_temperature(buf, value);
+        }
+        Malformed::MissingTemperature => {
+            buf.extend_from_slice(name);
+            buf.push(b';');
+        }
+        Malformed::NoDecimal => {
+            buf.extend_from_slice(name);
+            buf.push(b';');
+            buf.extend_from_slice((value / 10).to_string().as_bytes());
+        }
+        Malformed::TwoDecimals => {
+            buf.extend_from_slice(name);
+            buf.push(b';');
+            write_temperature(buf, value);
+            buf.push(b'5');
+        }
+        Malformed::OutOfRange => {
+            buf.extend_from_slice(name);
+            buf.push(b';');
+            buf.extend_from_slice(b"1");
+            write_temperature(buf, value.abs());
+        }
+        Malformed::LongName => {
+            let repeats = 101usize.div_ceil(name.len());
+            buf.extend_from_slice(&name.repeat(repeats));
+            buf.push(b';');
+            write_temperature(buf, value);
+        }
+        Malformed::CarriageReturn => {
+            buf.extend_from_slice(name);
+            buf.push(b';');
+            write_temperature(buf, value);
+            buf.push(b'\r');
+        }
+        Malformed::EmptyLine => {}
+        Malformed::ExtraField => {
+            buf.extend_from_slice(name);
+            buf.push(b';');
+            write_temperature(buf, value);
+            buf.extend_from_slice(b";1");
+        }
+    }
+    buf.push(b'\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn generate_to_string(options: &Options, chunk_rows: u64) -> String {
+        let mut out = Vec::new();
+        generate_with_chunk_rows(options, &mut out, chunk_rows).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// Whether `line` is a valid 1BRC line, without its newline.
+    fn is_valid(line: &str) -> bool {
+        let Some((name, value)) = line.split_once(';') else {
+            return false;
+        };
+        let digits = value.strip_prefix('-').unwrap_or(value);
+        let valid_digits = match digits.as_bytes() {
+            [i, b'.', d] | [b'1'..=b'9', i, b'.', d] => i.is_ascii_digit() && d.is_ascii_digit(),
+            _ => false,
+        };
+        (1..=100).contains(&name.len()) && !name.contains(['\r', '\n']) && valid_digits
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "too slow")]
+    fn deterministic_by_seed() {
+        let options = Options {
+            rows: 10_000,
+            seed: 7,
+            malformed: 0.01,
+            ..Options::default()
+        };
+        let expected = generate_to_string(&options, 1000);
+        for workers in [1, 3, 16] {
+            let actual = generate_to_string(
+                &Options {
+                    workers,
+                    ..options.clone()
+                },
+                1000,
+            );
+            assert!(actual == expected, "{workers} workers");
+        }
+        let other_seed = generate_to_string(
+            &Options {
+                seed: 8,
+                ..options.clone()
+            },
+            1000,
+        );
+        assert!(other_seed != expected);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "too slow")]
+    fn valid_lines_and_stations() {
+        for (stations, names) in [
+            (1, NameLengths::Real),
+            (413, NameLengths::Real),
+            (MAX_STATIONS, NameLengths::Real),
+            (50, NameLengths::Uniform { min: 1, max: 1 }),
+            (MAX_STATIONS, NameLengths::Uniform { min: 90, max: 100 }),
+        ] {
+            let options = Options {
+                rows: 200_000,
+                stations,
+                names,
+                workers: 4,
+                ..Options::default()
+            };
+            let output = generate_to_string(&options, 4096);
+            assert!(output.ends_with('\n'));
+            let mut seen = HashSet::new();
+            for line in output.split_terminator('\n') {
+                assert!(is_valid(line), "'{line}'");
+                let name = line.split_once(';').unwrap().0;
+                if let NameLengths::Uniform { min, max } = names {
+                    assert!((min..=max).contains(&name.len()), "'{name}'");
+                }
+                seen.insert(name);
+            }
+            assert_eq!(output.split_terminator('\n').count(), 200_000);
+            // every station appears with this many rows
+            assert_eq!(seen.len(), stations, "{stations} {names:?}");
+        }
+    }
+
+    #[test]
+    fn not_enough_names() {
+        let options = Options {
+            rows: 1,
+            stations: 1000,
+            names: NameLengths::Uniform { min: 1, max: 1 },
+            ..Options::default()
+        };
+        assert!(generate(&options, io::sink()).is_err());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "too slow")]
+    fn malformed_ratio() {
+        let options = Options {
+            rows: 100_000,
+            malformed: 0.1,
+            workers: 4,
+            ..Options::default()
+        };
+        let output = generate_to_string(&options, CHUNK_ROWS);
+        let invalid = output
+            .split_terminator('\n')
+            .filter(|line| !is_valid(line))
+            .count();
+        assert!((9_000..11_000).contains(&invalid), "{invalid}");
+    }
+}
@@ -0,0 +1,209 @@
+//! `--group-by`, the results of groups of stations with a key derived from their names, such as the
+//! country of `EU/DE/Berlin-Tegel`.
+//!
+//! The stations are aggregated as usual, and their results merged per group for the output, so
+//! several levels of groups (country, region, station) come from a single pass over the file.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::output::{StationResult, mean_tenths};
+
+/// How the key of the group of a station is derived from its name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GroupKey {
+    /// The station itself, no grouping
+    Station,
+    /// The part of the name before its `n`th separator, the whole name if it has fewer
+    Separator { separator: char, n: usize },
+    /// The first `n` characters of the name
+    Prefix(usize),
+    /// The group of each station from a mapping file, the stations it doesn't list being left out
+    Mapping(HashMap<String, String>),
+}
+impl GroupKey {
+    /// A mapping file, a `<station>;<group>` line per station.
+    pub fn mapping(contents: &str) -> Result<Self, String> {
+        let mut mapping = HashMap::new();
+        for (i, line) in contents.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let (station, group) = line
+                .rsplit_once(';')
+                .ok_or_else(|| format!("line {}: expected '<station>;<group>'", i + 1))?;
+            mapping.insert(station.to_string(), group.to_string());
+        }
+        Ok(GroupKey::Mapping(mapping))
+    }
+
+    /// The key of the group of the station `name`, `None` if it's in no group.
+    pub fn key<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        match self {
+            GroupKey::Station => Some(name),
+            GroupKey::Separator { separator, n } => Some(
+                name.match_indices(*separator)
+                    .nth(n - 1)
+                    .map_or(name, |(i, _)| &name[..i]),
+            ),
+            GroupKey::Prefix(n) => Some(
+                name.char_indices()
+                    .nth(*n)
+                    .map_or(name, |(i, _)| &name[..i]),
+            ),
+            GroupKey::Mapping(mapping) => mapping.get(name).map(String::as_str),
+        }
+    }
+}
+impl std::str::FromStr for GroupKey {
+    type Err = String;
+
+    /// `station`, `sep:<separator>:<n>` or `prefix:<n>`. Mapping files are read with
+    /// [`GroupKey::mapping`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let positive = |n: &str| match n.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("expected a positive number, got '{n}'")),
+        };
+        if s == "station" {
+            Ok(GroupKey::Station)
+        } else if let Some(rest) = s.strip_prefix("sep:") {
+            let (separator, n) = rest
+                .rsplit_once(':')
+                .ok_or_else(|| format!("expected 'sep:<separator>:<n>', got '{s}'"))?;
+            let mut chars = separator.chars();
+            let (Some(separator), None) = (chars.next(), chars.next()) else {
+                return Err(format!(
+                    "the separator must be one character, got '{separator}'"
+                ));
+            };
+            Ok(GroupKey::Separator {
+                separator,
+                n: positive(n)?,
+            })
+        } else if let Some(n) = s.strip_prefix("prefix:") {
+            Ok(GroupKey::Prefix(positive(n)?))
+        } else {
+            Err(format!(
+                "unknown grouping '{s}', expected station, sep:<separator>:<n>, prefix:<n> or map:<file>"
+            ))
+        }
+    }
+}
+
+/// Merge the results of the stations of each group, in no particular order.
+pub fn group_results<'a>(
+    results: &[StationResult<'a>],
+    key: &'a GroupKey,
+) -> Vec<StationResult<'a>> {
+    if *key == GroupKey::Station {
+        return results.to_vec();
+    }
+    merge_results(results, |result| match &result.name {
+        Cow::Borrowed(name) => key.key(name).map(Cow::Borrowed),
+        Cow::Owned(name) => key.key(name).map(|group| Cow::Owned(group.to_string())),
+    })
+}
+
+/// Merge the results with the same key, in no particular order, leaving out those without one.
+pub fn merge_results<'a, 'r>(
+    results: impl IntoIterator<Item = &'r StationResult<'a>>,
+    key: impl Fn(&StationResult<'a>) -> Option<Cow<'a, str>>,
+) -> Vec<StationResult<'a>>
+where
+    'a: 'r,
+{
+    let mut groups = HashMap::<Cow<str>, StationResult>::new();
+    for result in results {
+        let Some(name) = key(result) else {
+            continue;
+        };
+        groups
+            .entry(name.clone())
+            .and_modify(|group| {
+                group.min = group.min.min(result.min);
+                group.max = group.max.max(result.max);
+                group.sum_tenths += result.sum_tenths;
+                group.count += result.count;
+                group.outliers += result.outliers;
+            })
+            .or_insert(StationResult {
+                name,
+                ..result.clone()
+            });
+    }
+    groups
+        .into_values()
+        .map(|group| StationResult {
+            mean: mean_tenths(group.sum_tenths, group.count) as f64 / 10.0,
+            ..group
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        let sep = |n| GroupKey::Separator { separator: '/', n };
+        assert_eq!(sep(1).key("EU/DE/Berlin-Tegel"), Some("EU"));
+        assert_eq!(sep(2).key("EU/DE/Berlin-Tegel"), Some("EU/DE"));
+        assert_eq!(sep(3).key("EU/DE/Berlin-Tegel"), Some("EU/DE/Berlin-Tegel"));
+        assert_eq!(sep(1).key("Berlin"), Some("Berlin"));
+        assert_eq!(GroupKey::Prefix(2).key("Zürich"), Some("Zü"));
+        assert_eq!(GroupKey::Prefix(10).key("Zürich"), Some("Zürich"));
+        let mapping = GroupKey::mapping("Zürich;CH\nBern;CH\n\nAbha;SA\n").unwrap();
+        assert_eq!(mapping.key("Bern"), Some("CH"));
+        assert_eq!(mapping.key("Paris"), None);
+
+        assert_eq!("sep:/:2".parse(), Ok(sep(2)));
+        assert_eq!(
+            "sep:::1".parse(),
+            Ok(GroupKey::Separator {
+                separator: ':',
+                n: 1
+            })
+        );
+        assert_eq!("prefix:3".parse(), Ok(GroupKey::Prefix(3)));
+        assert_eq!("station".parse(), Ok(GroupKey::Station));
+        assert!("sep:/:0".parse::<GroupKey>().is_err());
+        assert!("sep:ab:1".parse::<GroupKey>().is_err());
+        assert!(GroupKey::mapping("Bern").is_err());
+    }
+
+    #[test]
+    fn merged_results() {
+        let result = |name: &'static str, min, sum_tenths, max, count| StationResult {
+            name: name.into(),
+            min,
+            mean: mean_tenths(sum_tenths, count) as f64 / 10.0,
+            max,
+            count,
+            sum_tenths,
+            outliers: count / 2,
+        };
+        let stations = [
+            result("EU/DE/Berlin", -5.0, 100, 20.0, 10),
+            result("EU/DE/Bonn", -2.0, 35, 25.0, 4),
+            result("EU/FR/Paris", -10.0, -7, 18.0, 3),
+            result("AS/JP/Tokyo", 1.0, 50, 30.0, 2),
+        ];
+        let key = GroupKey::Separator {
+            separator: '/',
+            n: 1,
+        };
+        let mut groups = group_results(&stations, &key);
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(
+            groups,
+            [
+                result("AS", 1.0, 50, 30.0, 2),
+                // 5 + 2 + 1 outliers, and a mean of 128 / 17 = 7.53 tenths
+                result("EU", -10.0, 128, 25.0, 17),
+            ]
+        );
+        assert_eq!(groups[1].mean, 0.8);
+    }
+}
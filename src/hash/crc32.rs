@@ -0,0 +1,134 @@
+use std::hash::{BuildHasher, Hasher};
+
+/// CRC32C (Castagnoli) hash.
+///
+/// Uses the SSE4.2 `crc32` instruction when the CPU supports it, detected at runtime (see
+/// [`Crc32Hash::default`]), and a table based scalar implementation otherwise. Both produce the same
+/// hash values.
+#[derive(Clone, Copy)]
+pub struct Crc32Hash {
+    sse42: bool,
+}
+impl Default for Crc32Hash {
+    /// Detects SSE4.2 when the map is created, so the hashers only branch on a flag that never
+    /// changes.
+    fn default() -> Self {
+        #[cfg(all(target_arch = "x86_64", not(miri)))]
+        let sse42 = std::arch::is_x86_feature_detected!("sse4.2");
+        #[cfg(not(all(target_arch = "x86_64", not(miri))))]
+        let sse42 = false;
+        Self { sse42 }
+    }
+}
+impl BuildHasher for Crc32Hash {
+    type Hasher = Crc32Hasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        Crc32Hasher {
+            crc: 0xc35e3d1d,
+            sse42: self.sse42,
+        }
+    }
+}
+
+pub struct Crc32Hasher {
+    crc: u32,
+    sse42: bool,
+}
+impl Hasher for Crc32Hasher {
+    #[inline(always)]
+    fn write_u64(&mut self, i: u64) {
+        self.crc = crc32c_u64(self.crc, i, self.sse42);
+    }
+
+    #[inline(always)]
+    fn write(&mut self, bytes: &[u8]) {
+        super::write_words(self, bytes);
+    }
+
+    #[inline(always)]
+    fn finish(&self) -> u64 {
+        // CRC32 is well distributed in all its 32 bits, no need for extra mixing
+        self.crc as u64
+    }
+
+    #[inline(always)]
+    fn write_u8(&mut self, i: u8) {
+        self.write_u64(i as u64);
+    }
+    #[inline(always)]
+    fn write_u16(&mut self, i: u16) {
+        self.write_u64(i as u64);
+    }
+    #[inline(always)]
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64);
+    }
+    #[inline(always)]
+    fn write_u128(&mut self, i: u128) {
+        self.write_u64(i as u64);
+        self.write_u64((i >> 64) as u64);
+    }
+    #[inline(always)]
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+/// The hardware CRC if `sse42`, always the case when the binary is compiled with SSE4.2 enabled
+/// (e.g. `-Ctarget-cpu=native`). In the AVX2 and AVX-512 variants of the main loop, which imply
+/// SSE4.2, the `crc32` instruction is inlined.
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+fn crc32c_u64(crc: u32, word: u64, sse42: bool) -> u32 {
+    if cfg!(target_feature = "sse4.2") || sse42 {
+        unsafe { crc32c_u64_sse42(crc, word) }
+    } else {
+        crc32c_u64_scalar(crc, word)
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+#[inline(always)]
+fn crc32c_u64(crc: u32, word: u64, _sse42: bool) -> u32 {
+    crc32c_u64_scalar(crc, word)
+}
+
+/// # Safety
+///
+/// The CPU must support SSE4.2.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.2")]
+#[inline]
+pub(super) unsafe fn crc32c_u64_sse42(crc: u32, word: u64) -> u32 {
+    std::arch::x86_64::_mm_crc32_u64(crc as u64, word) as u32
+}
+
+#[inline(always)]
+pub(super) fn crc32c_u64_scalar(mut crc: u32, word: u64) -> u32 {
+    for b in word.to_le_bytes() {
+        crc = CRC32C_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+static CRC32C_TABLE: [u32; 256] = {
+    const POLY: u32 = 0x82f63b78; // reflected Castagnoli polynomial
+    let mut table = [0_u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
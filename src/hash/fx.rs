@@ -0,0 +1,58 @@
+use std::hash::{BuildHasher, Hasher};
+
+// Same constant as rustc's FxHasher
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+#[derive(Default)]
+pub struct FxHash;
+impl BuildHasher for FxHash {
+    type Hasher = FxHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        FxHasher(0)
+    }
+}
+
+/// FxHash-style hasher, as used by rustc.
+///
+/// The final rotation moves the high bits of the last multiplication into the low bits,
+/// which are the ones used to index the hash map table.
+pub struct FxHasher(u64);
+impl Hasher for FxHasher {
+    #[inline(always)]
+    fn write_u64(&mut self, i: u64) {
+        self.0 = (self.0.rotate_left(5) ^ i).wrapping_mul(SEED);
+    }
+
+    #[inline(always)]
+    fn write(&mut self, bytes: &[u8]) {
+        super::write_words(self, bytes);
+    }
+
+    #[inline(always)]
+    fn finish(&self) -> u64 {
+        self.0.rotate_left(26)
+    }
+
+    #[inline(always)]
+    fn write_u8(&mut self, i: u8) {
+        self.write_u64(i as u64);
+    }
+    #[inline(always)]
+    fn write_u16(&mut self, i: u16) {
+        self.write_u64(i as u64);
+    }
+    #[inline(always)]
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64);
+    }
+    #[inline(always)]
+    fn write_u128(&mut self, i: u128) {
+        self.write_u64(i as u64);
+        self.write_u64((i >> 64) as u64);
+    }
+    #[inline(always)]
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
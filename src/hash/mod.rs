@@ -0,0 +1,312 @@
+mod crc32;
+mod fx;
+mod mulrot;
+mod secure;
+mod xor;
+
+use std::hash::Hasher;
+
+pub use crc32::Crc32Hash;
+pub use fx::FxHash;
+pub use mulrot::MulRotHash;
+pub use secure::SecureHash;
+pub use xor::XorHash;
+
+/// The hash functions that can be used for the station names hash map, selected with `--hash`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashKind {
+    #[default]
+    Xor,
+    MulRot,
+    Fx,
+    Crc32,
+    /// Randomly seeded per run, for untrusted input
+    Secure,
+}
+impl HashKind {
+    pub const ALL: [HashKind; 5] = [
+        HashKind::Xor,
+        HashKind::MulRot,
+        HashKind::Fx,
+        HashKind::Crc32,
+        HashKind::Secure,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HashKind::Xor => "xor",
+            HashKind::MulRot => "mulrot",
+            HashKind::Fx => "fx",
+            HashKind::Crc32 => "crc32",
+            HashKind::Secure => "secure",
+        }
+    }
+}
+impl std::str::FromStr for HashKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| format!("unknown hash function '{s}'"))
+    }
+}
+
+/// Write `bytes` as a sequence of u64 words, the last one zero padded.
+#[inline(always)]
+fn write_words(hasher: &mut impl Hasher, bytes: &[u8]) {
+    let (words, tail) = bytes.as_chunks::<8>();
+    for word in words {
+        hasher.write_u64(u64::from_ne_bytes(*word));
+    }
+    if !tail.is_empty() {
+        let mut word = [0_u8; 8];
+        word[..tail.len()].copy_from_slice(tail);
+        hasher.write_u64(u64::from_ne_bytes(word));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::hash::BuildHasher;
+
+    use super::*;
+    use crate::StationName;
+    use crate::hashmap::SimpleHashMap;
+    use crate::test_utils::station_names;
+
+    /// Deterministic alphanumeric 8-byte blocks.
+    fn blocks(n: usize, seed: u64) -> Vec<[u8; 8]> {
+        const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+        let mut state = seed;
+        (0..n)
+            .map(|_| {
+                std::array::from_fn(|_| {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    ALPHABET[((state >> 33) % ALPHABET.len() as u64) as usize]
+                })
+            })
+            .collect()
+    }
+
+    /// Names that are permutations of the same 8-byte blocks.
+    pub(crate) fn permuted_blocks_names() -> Vec<Vec<u8>> {
+        let mut names = Vec::new();
+        for set in blocks(4 * 40, 1).as_chunks::<4>().0 {
+            for a in 0..4 {
+                for b in (0..4).filter(|&b| b != a) {
+                    for c in (0..4).filter(|&c| c != a && c != b) {
+                        let d = 6 - a - b - c;
+                        names.push([set[a], set[b], set[c], set[d]].concat());
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    /// Names of two 8-byte chunks whose xor is always the same.
+    pub(crate) fn xor_equal_names() -> Vec<Vec<u8>> {
+        // xor of two alphanumeric bytes with the same 0x40 and 0x20 bits stays alphanumeric-ish,
+        // but any byte except ';' and '\n' is a valid name byte, so we don't care.
+        let diff = u64::from_ne_bytes(*b"\x01\x02\x03\x04\x05\x06\x07\x08");
+        blocks(2000, 2)
+            .into_iter()
+            .map(|block| {
+                let a = u64::from_ne_bytes(block);
+                [a.to_ne_bytes(), (a ^ diff).to_ne_bytes()].concat()
+            })
+            .collect()
+    }
+
+    struct Quality {
+        /// Number of names that share their full 64-bit hash with a previous name
+        hash_collisions: usize,
+        /// Number of names that ended up in the hash map fallback
+        fallback_size: usize,
+    }
+
+    fn quality<S: BuildHasher + Default>(names: &[Vec<u8>]) -> Quality {
+        let names = names
+            .iter()
+            .map(|name| StationName::new(name))
+            .collect::<Vec<_>>();
+        let hasher = S::default();
+        let mut hashes = HashSet::new();
+        let hash_collisions = names
+            .iter()
+            .filter(|name| !hashes.insert(hasher.hash_one(name)))
+            .count();
+
+        // same parameters as `parse_file_bytes`
+        let mut map = SimpleHashMap::<StationName, u32, S>::new(1000, 128.0);
+        for name in &names {
+            *map.get_or_default(*name) += 1;
+        }
+        assert_eq!(map.iter().count(), names.len());
+        Quality {
+            hash_collisions,
+            fallback_size: map.stats().fallback_size,
+        }
+    }
+
+    /// Upper bound on the fallback size we accept for `n` names: twice the expected number of bucket
+    /// collisions of a uniformly random hash function, plus some slack for small `n`.
+    fn max_fallback_size(n: usize) -> usize {
+        let buckets = (1000.0 * 128.0_f64).log2().ceil().exp2();
+        let expected = n as f64 - buckets * (1.0 - (1.0 - 1.0 / buckets).powi(n as i32));
+        (2.0 * expected) as usize + 4
+    }
+
+    fn quality_of(kind: HashKind, names: &[Vec<u8>]) -> Quality {
+        match kind {
+            HashKind::Xor => quality::<XorHash>(names),
+            HashKind::MulRot => quality::<MulRotHash>(names),
+            HashKind::Fx => quality::<FxHash>(names),
+            HashKind::Crc32 => quality::<Crc32Hash>(names),
+            HashKind::Secure => quality::<SecureHash>(names),
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "hash quality, no unsafe code involved")]
+    fn stations_list() {
+        let names = station_names();
+        assert_eq!(names.len(), 413);
+        for kind in HashKind::ALL {
+            let q = quality_of(kind, &names);
+            assert_eq!(q.hash_collisions, 0, "{kind:?}");
+            assert!(
+                q.fallback_size <= max_fallback_size(names.len()),
+                "{kind:?}: {}",
+                q.fallback_size
+            );
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "hash quality, no unsafe code involved")]
+    fn permuted_blocks() {
+        let names = permuted_blocks_names();
+        for kind in HashKind::ALL {
+            let q = quality_of(kind, &names);
+            if kind == HashKind::Xor {
+                // order of the words doesn't matter for xor, all permutations collide
+                assert_eq!(q.hash_collisions, names.len() / 24 * 23);
+            } else {
+                assert_eq!(q.hash_collisions, 0, "{kind:?}");
+                assert!(
+                    q.fallback_size <= max_fallback_size(names.len()),
+                    "{kind:?}: {}",
+                    q.fallback_size
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "hash quality, no unsafe code involved")]
+    fn xor_equal_chunks() {
+        let names = xor_equal_names();
+        for kind in HashKind::ALL {
+            let q = quality_of(kind, &names);
+            if kind == HashKind::Xor {
+                // all names hash to the same value
+                assert_eq!(q.hash_collisions, names.len() - 1);
+            } else {
+                assert_eq!(q.hash_collisions, 0, "{kind:?}");
+                assert!(
+                    q.fallback_size <= max_fallback_size(names.len()),
+                    "{kind:?}: {}",
+                    q.fallback_size
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "hash quality, no unsafe code involved")]
+    fn secure_hash_resists_adversarial_sets() {
+        for names in [permuted_blocks_names(), xor_equal_names()] {
+            // the sets degenerate with the xor hash: at least half the names go to the fallback
+            assert!(quality::<XorHash>(&names).fallback_size >= names.len() / 2);
+
+            let q = quality::<SecureHash>(&names);
+            assert_eq!(q.hash_collisions, 0);
+            assert!(q.fallback_size <= max_fallback_size(names.len()));
+        }
+    }
+
+    #[test]
+    fn secure_hash_depends_on_seed() {
+        let names = station_names();
+        let (a, b) = (SecureHash::with_seed(1, 3), SecureHash::with_seed(2, 5));
+        let differ = names
+            .iter()
+            .filter(|name| {
+                let name = StationName::new(name);
+                a.hash_one(name) != b.hash_one(name)
+            })
+            .count();
+        assert_eq!(differ, names.len());
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn crc32_scalar_matches_hardware() {
+        if !std::is_x86_feature_detected!("sse4.2") {
+            return;
+        }
+        for (i, block) in blocks(1000, 3).into_iter().enumerate() {
+            let (crc, word) = (
+                (i as u32).wrapping_mul(0x9e3779b9),
+                u64::from_ne_bytes(block),
+            );
+            assert_eq!(
+                unsafe { crc32::crc32c_u64_sse42(crc, word) },
+                crc32::crc32c_u64_scalar(crc, word)
+            );
+        }
+    }
+
+    /// Rough throughput of each hasher over the station names.
+    /// Run with `cargo test --release -- --ignored --nocapture bench_hashers`.
+    #[test]
+    #[ignore]
+    fn bench_hashers() {
+        fn bench<S: BuildHasher + Default>(kind: HashKind, names: &[StationName]) {
+            const ROUNDS: usize = 10_000;
+            let hasher = S::default();
+            let start = std::time::Instant::now();
+            let mut acc = 0_u64;
+            for _ in 0..ROUNDS {
+                for name in names {
+                    acc ^= hasher.hash_one(std::hint::black_box(name));
+                }
+            }
+            let elapsed = start.elapsed();
+            std::hint::black_box(acc);
+            let per_name = elapsed.as_nanos() as f64 / (ROUNDS * names.len()) as f64;
+            println!("{:>8}: {per_name:.2} ns/name", kind.name());
+        }
+
+        let names = station_names();
+        let names = names
+            .iter()
+            .map(|name| StationName::new(name))
+            .collect::<Vec<_>>();
+        for kind in HashKind::ALL {
+            match kind {
+                HashKind::Xor => bench::<XorHash>(kind, &names),
+                HashKind::MulRot => bench::<MulRotHash>(kind, &names),
+                HashKind::Fx => bench::<FxHash>(kind, &names),
+                HashKind::Crc32 => bench::<Crc32Hash>(kind, &names),
+                HashKind::Secure => bench::<SecureHash>(kind, &names),
+            }
+        }
+    }
+}
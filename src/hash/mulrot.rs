@@ -0,0 +1,59 @@
+use std::hash::{BuildHasher, Hasher};
+
+const MUL: u64 = 0x9e3779b97f4a7c15;
+
+#[derive(Default)]
+pub struct MulRotHash;
+impl BuildHasher for MulRotHash {
+    type Hasher = MulRotHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        MulRotHasher(0xd13c02cbc35e3d1d)
+    }
+}
+
+/// Multiply-rotate hasher.
+///
+/// Each word is xored into the state, which is then multiplied by an odd constant and rotated,
+/// so that the high (well mixed) bits of the product feed the low bits of the next round.
+/// Unlike [`XorHasher`](super::xor::XorHasher), the order of the words matters.
+pub struct MulRotHasher(u64);
+impl Hasher for MulRotHasher {
+    #[inline(always)]
+    fn write_u64(&mut self, i: u64) {
+        self.0 = (self.0 ^ i).wrapping_mul(MUL).rotate_left(29);
+    }
+
+    #[inline(always)]
+    fn write(&mut self, bytes: &[u8]) {
+        super::write_words(self, bytes);
+    }
+
+    #[inline(always)]
+    fn finish(&self) -> u64 {
+        let hash = (self.0 ^ (self.0 >> 32)).wrapping_mul(MUL);
+        hash ^ (hash >> 29)
+    }
+
+    #[inline(always)]
+    fn write_u8(&mut self, i: u8) {
+        self.write_u64(i as u64);
+    }
+    #[inline(always)]
+    fn write_u16(&mut self, i: u16) {
+        self.write_u64(i as u64);
+    }
+    #[inline(always)]
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64);
+    }
+    #[inline(always)]
+    fn write_u128(&mut self, i: u128) {
+        self.write_u64(i as u64);
+        self.write_u64((i >> 64) as u64);
+    }
+    #[inline(always)]
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
@@ -0,0 +1,91 @@
+use std::hash::{BuildHasher, Hasher};
+use std::sync::OnceLock;
+
+/// Keyed hash intended for untrusted input.
+///
+/// The keys are drawn randomly once per run (see [`SecureHash::default`]), and the mixing is
+/// non-linear in both the input and the keys, so a set of colliding station names can't be crafted
+/// in advance, as is trivially possible with [`XorHash`](super::XorHash).
+#[derive(Clone, Copy)]
+pub struct SecureHash {
+    k0: u64,
+    k1: u64,
+}
+impl SecureHash {
+    pub fn with_seed(k0: u64, k1: u64) -> Self {
+        // the multiplier must not be zero, or every word would map to the same state
+        Self { k0, k1: k1 | 1 }
+    }
+}
+impl Default for SecureHash {
+    /// Uses the per-run random seed, so all hash maps of a single run hash the same way.
+    fn default() -> Self {
+        static SEED: OnceLock<(u64, u64)> = OnceLock::new();
+        let (k0, k1) = *SEED.get_or_init(|| {
+            // the std RandomState is seeded from the OS randomness source
+            let state = std::hash::RandomState::new();
+            (state.hash_one(0_u64), state.hash_one(1_u64))
+        });
+        Self::with_seed(k0, k1)
+    }
+}
+impl BuildHasher for SecureHash {
+    type Hasher = SecureHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        SecureHasher {
+            state: self.k0,
+            key: self.k1,
+        }
+    }
+}
+
+pub struct SecureHasher {
+    state: u64,
+    key: u64,
+}
+impl Hasher for SecureHasher {
+    #[inline(always)]
+    fn write_u64(&mut self, i: u64) {
+        self.state = folded_multiply(self.state ^ i, self.key);
+    }
+
+    #[inline(always)]
+    fn write(&mut self, bytes: &[u8]) {
+        super::write_words(self, bytes);
+    }
+
+    #[inline(always)]
+    fn finish(&self) -> u64 {
+        folded_multiply(self.state, self.key.rotate_left(32) | 1)
+    }
+
+    #[inline(always)]
+    fn write_u8(&mut self, i: u8) {
+        self.write_u64(i as u64);
+    }
+    #[inline(always)]
+    fn write_u16(&mut self, i: u16) {
+        self.write_u64(i as u64);
+    }
+    #[inline(always)]
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64);
+    }
+    #[inline(always)]
+    fn write_u128(&mut self, i: u128) {
+        self.write_u64(i as u64);
+        self.write_u64((i >> 64) as u64);
+    }
+    #[inline(always)]
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+/// Full 64x64->128 multiplication, with the two halves of the result xored together.
+#[inline(always)]
+fn folded_multiply(x: u64, y: u64) -> u64 {
+    let full = x as u128 * y as u128;
+    (full as u64) ^ ((full >> 64) as u64)
+}
@@ -1,7 +1,7 @@
 use std::hash::{BuildHasher, Hasher};
 
 #[derive(Default)]
-pub(crate) struct XorHash;
+pub struct XorHash;
 impl BuildHasher for XorHash {
     type Hasher = XorHasher;
 
@@ -10,7 +10,7 @@ impl BuildHasher for XorHash {
     }
 }
 
-pub(crate) struct XorHasher(u64);
+pub struct XorHasher(u64);
 impl Hasher for XorHasher {
     #[inline(always)]
     fn write_u64(&mut self, i: u64) {
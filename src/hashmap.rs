@@ -1,11 +1,13 @@
 use std::mem::MaybeUninit;
 
-pub(crate) struct SimpleHashMap<K, V, S = std::hash::RandomState> {
+pub struct SimpleHashMap<K, V, S = std::hash::RandomState> {
     hasher: S,
     table_mask: u64,
     table: Box<[Entry<K, V>]>,
     #[allow(clippy::box_collection)]
     fallback: Box<std::collections::HashMap<K, V, S>>,
+    // Number of `get_or_default` and `get_mut` calls that went to the fallback map
+    fallback_hits: u64,
 }
 struct Entry<K, V> {
     // Vacant,
@@ -22,7 +24,10 @@ impl<K, V, S> SimpleHashMap<K, V, S> {
     where
         S: Default,
     {
-        let max_capacity = ((max_capacity as f32 * safety_factor) as usize).next_power_of_two();
+        // at least 2 buckets, since bucket 0 is reserved and marked as occupied with hash 1
+        let max_capacity = ((max_capacity as f32 * safety_factor) as usize)
+            .next_power_of_two()
+            .max(2);
         let mut table = (0..max_capacity)
             .map(|_| Entry {
                 hash: 0,
@@ -41,6 +46,7 @@ impl<K, V, S> SimpleHashMap<K, V, S> {
                 fallback_capacity,
                 S::default(),
             )),
+            fallback_hits: 0,
         }
     }
 
@@ -55,14 +61,14 @@ impl<K, V, S> SimpleHashMap<K, V, S> {
         let (key, hash) = (pair.key, pair.hash);
 
         let bucket = (hash & self.table_mask) as usize;
-        unsafe { std::hint::assert_unchecked(bucket < self.table.len()) };
-        if std::hint::likely(self.table[bucket].hash == hash)
-            && std::hint::likely(key == unsafe { self.table[bucket].kv.assume_init_ref() }.key)
+        unsafe { crate::hint::assert_unchecked(bucket < self.table.len()) };
+        if crate::hint::likely(self.table[bucket].hash == hash)
+            && crate::hint::likely(key == unsafe { self.table[bucket].kv.assume_init_ref() }.key)
         {
             return &mut unsafe { self.table[bucket].kv.assume_init_mut() }.value;
         }
 
-        if std::hint::unlikely(self.table[bucket].hash != 0) {
+        if crate::hint::unlikely(self.table[bucket].hash != 0) {
             // - if bucket is occupied by a different key
             // - if we hit bucket 0, possibly because the key hash is 0
             return self.get_or_default_fallback(key);
@@ -86,9 +92,45 @@ impl<K, V, S> SimpleHashMap<K, V, S> {
         V: Default,
         S: std::hash::BuildHasher,
     {
+        self.fallback_hits += 1;
         self.fallback.entry(key).or_default()
     }
 
+    /// The value of `key`, without inserting it if it's missing.
+    #[inline(always)]
+    pub fn get_mut(&mut self, key: impl Key<K>) -> Option<&mut V>
+    where
+        K: std::hash::Hash + Eq,
+        S: std::hash::BuildHasher,
+    {
+        let pair = key.into_key_and_hash(&self.hasher);
+        let (key, hash) = (pair.key, pair.hash);
+
+        let bucket = (hash & self.table_mask) as usize;
+        unsafe { crate::hint::assert_unchecked(bucket < self.table.len()) };
+        if crate::hint::likely(self.table[bucket].hash == hash)
+            && crate::hint::likely(key == unsafe { self.table[bucket].kv.assume_init_ref() }.key)
+        {
+            return Some(&mut unsafe { self.table[bucket].kv.assume_init_mut() }.value);
+        }
+
+        if self.table[bucket].hash != 0 {
+            // not cold, as missing keys may often share a bucket with a present one
+            return self.get_mut_fallback(&key);
+        }
+        None
+    }
+
+    #[inline(never)]
+    fn get_mut_fallback(&mut self, key: &K) -> Option<&mut V>
+    where
+        K: std::hash::Hash + Eq,
+        S: std::hash::BuildHasher,
+    {
+        self.fallback_hits += 1;
+        self.fallback.get_mut(key)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
         struct Iter<'a, K, V> {
             table: &'a [Entry<K, V>],
@@ -123,23 +165,74 @@ impl<K, V, S> SimpleHashMap<K, V, S> {
         &self.hasher
     }
 
-    pub(crate) fn fallback_size(&self) -> usize {
-        self.fallback.len()
+    pub fn stats(&self) -> HashMapStats {
+        HashMapStats {
+            capacity: self.table.len() - 1, // we dont use bucket 0
+            occupied: self.table[1..].iter().filter(|e| e.hash != 0).count(),
+            fallback_size: self.fallback.len(),
+            fallback_hits: self.fallback_hits,
+        }
+    }
+
+    /// Pairs of keys that collided: each key in the fallback, with the key occupying its bucket in the
+    /// table (or `None` if its hash maps to the reserved bucket 0).
+    pub(crate) fn collisions(&self) -> impl Iterator<Item = (&K, Option<&K>)>
+    where
+        K: std::hash::Hash,
+        S: std::hash::BuildHasher,
+    {
+        self.fallback.keys().map(|key| {
+            let bucket = (self.hasher.hash_one(key) & self.table_mask) as usize;
+            let entry = &self.table[bucket];
+            let table_key = (bucket != 0 && entry.hash != 0)
+                .then(|| &unsafe { entry.kv.assume_init_ref() }.key);
+            (key, table_key)
+        })
+    }
+}
+
+impl<K, V, S> Drop for SimpleHashMap<K, V, S> {
+    fn drop(&mut self) {
+        if std::mem::needs_drop::<EntryKV<K, V>>() {
+            for entry in self.table[1..].iter_mut().filter(|e| e.hash != 0) {
+                unsafe { entry.kv.assume_init_drop() };
+            }
+        }
     }
 }
 
+#[derive(Clone, Copy, Default)]
+pub struct HashMapStats {
+    /// Number of buckets in the table
+    pub capacity: usize,
+    /// Number of occupied buckets in the table
+    pub occupied: usize,
+    /// Number of entries in the fallback map
+    pub fallback_size: usize,
+    /// Number of `get_or_default` and `get_mut` calls that went to the fallback map
+    pub fallback_hits: u64,
+}
+
 #[derive(Clone, Copy)]
-pub(crate) struct KeyHashPair<K> {
+pub struct KeyHashPair<K> {
     pub key: K,
     hash: u64,
 }
 impl<K> KeyHashPair<K> {
+    /// # Safety
+    ///
+    /// `hash` must be the hash of `key` with the hasher of the map it is used with, as computed by
+    /// [`BuildHasher::hash_one`](std::hash::BuildHasher::hash_one).
     pub unsafe fn new_unchecked(key: K, hash: u64) -> Self {
         Self { key, hash }
     }
+
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
 }
 
-pub(crate) trait Key<K> {
+pub trait Key<K> {
     fn into_key_and_hash<S>(self, hasher: &S) -> KeyHashPair<K>
     where
         S: std::hash::BuildHasher;
@@ -168,3 +261,38 @@ where
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StationName;
+    use crate::hash::XorHash;
+
+    #[test]
+    fn stats_count_collisions() {
+        // the same two 8-byte words in a different order, so the same hash under `XorHash`
+        let (a, b) = (
+            StationName::new(b"Hamburg,Toronto,"),
+            StationName::new(b"Toronto,Hamburg,"),
+        );
+        let c = StationName::new(b"c");
+        let mut map = SimpleHashMap::<StationName, u32, XorHash>::new(16, 4.0);
+        for name in [a, b, b, c] {
+            *map.get_or_default(name) += 1;
+        }
+        assert_eq!(map.get_mut(b), Some(&mut 2));
+        assert_eq!(map.get_mut(StationName::new(b"ccccccccdddddddd")), None);
+
+        let stats = map.stats();
+        assert_eq!(stats.capacity, 63);
+        assert_eq!(stats.occupied, 2);
+        assert_eq!(stats.fallback_size, 1);
+        // the two `get_or_default` and the `get_mut` of `b`
+        assert_eq!(stats.fallback_hits, 3);
+        let collisions = map
+            .collisions()
+            .map(|(name, table_name)| (name.to_str(), table_name.map(|n| n.to_str())))
+            .collect::<Vec<_>>();
+        assert_eq!(collisions, [(b.to_str(), Some(a.to_str()))]);
+    }
+}
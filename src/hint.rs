@@ -0,0 +1,45 @@
+//! Branch layout and optimizer hints.
+//!
+//! With the `nightly` feature these are the `std::hint` intrinsics. On stable, the unlikely branch
+//! calls an empty `#[cold]` function, which has the same effect on the generated code in practice.
+
+#[cfg(feature = "nightly")]
+pub(crate) use std::hint::{likely, unlikely};
+
+#[cfg(not(feature = "nightly"))]
+#[inline(always)]
+#[cold]
+fn cold_path() {}
+
+#[cfg(not(feature = "nightly"))]
+#[inline(always)]
+pub(crate) fn likely(b: bool) -> bool {
+    if !b {
+        cold_path();
+    }
+    b
+}
+
+#[cfg(not(feature = "nightly"))]
+#[inline(always)]
+pub(crate) fn unlikely(b: bool) -> bool {
+    if b {
+        cold_path();
+    }
+    b
+}
+
+/// [`std::hint::assert_unchecked`], or a regular assertion with the `checked` feature.
+///
+/// # Safety
+///
+/// `cond` must be true.
+#[inline(always)]
+pub(crate) unsafe fn assert_unchecked(cond: bool) {
+    #[cfg(feature = "checked")]
+    assert!(cond);
+    #[cfg(not(feature = "checked"))]
+    unsafe {
+        std::hint::assert_unchecked(cond)
+    };
+}
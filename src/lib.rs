@@ -0,0 +1,1701 @@
+#![cfg_attr(feature = "nightly", feature(portable_simd))]
+#![cfg_attr(feature = "nightly", feature(likely_unlikely))]
+
+mod collation;
+pub mod cpu;
+pub mod format;
+pub mod generate;
+pub mod group;
+pub mod hash;
+pub mod hashmap;
+mod hint;
+pub mod output;
+mod progress;
+pub mod range;
+mod read;
+pub mod reference;
+mod rng;
+pub mod series;
+mod simd;
+mod stats;
+mod temperature;
+#[cfg(test)]
+mod test_utils;
+#[cfg(test)]
+mod tests;
+pub mod timings;
+
+use core::str;
+use std::borrow::Cow;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::cpu::CpuLevel;
+use crate::format::Separators;
+use crate::hash::{Crc32Hash, FxHash, HashKind, MulRotHash, SecureHash, XorHash};
+use crate::hashmap::{HashMapStats, Key, KeyHashPair, SimpleHashMap};
+use crate::output::StationResult;
+use crate::progress::Progress;
+use crate::range::{Bounded, MeasurementRange, RangePolicy, Unbounded};
+use crate::read::read;
+use crate::series::{BucketWidth, SeriesResult};
+use crate::simd::{ByteSearch, DefaultSearch};
+use crate::temperature::{Batch, DecodeBatch};
+use crate::timings::{EngineTimings, Stopwatch, Timing, WorkerTimings};
+
+// the parsing code reads the file as little endian words
+const _: () = assert!(cfg!(target_endian = "little"));
+
+/// Number of bytes after the start of a line that the main loop may read without bounds checks.
+const READ_AHEAD: usize = 128;
+
+/// Number of u64 words the station name slow path (names of 16 bytes or more) scans for the semicolon
+/// in each iteration.
+const NAME_STEP_WORDS: usize = 4;
+const _: () = {
+    // the 1BRC spec limits names to 100 bytes, and the temperature that follows is at most 6 bytes.
+    // the slow path scan may read up to a full step past the semicolon
+    let max_name_read = 16 + (100 - 16usize).div_ceil(NAME_STEP_WORDS * 8) * NAME_STEP_WORDS * 8;
+    assert!(max_name_read <= READ_AHEAD && 100 + 1 + 8 <= READ_AHEAD);
+};
+
+type HashMap<'a, S, V = StationSummary> = SimpleHashMap<StationName<'a>, V, S>;
+
+/// The map of `--bucket`, keyed by station and time bucket.
+type SeriesMap<'a, S> = SimpleHashMap<StationBucket<'a>, StationSummary, S>;
+
+/// Capacity of the per worker hash map, relative to the expected number of stations (1000).
+/// Under Miri allocating and scanning the large table dominates, and a small one also exercises the
+/// fallback map.
+const HASHMAP_SAFETY_FACTOR: f32 = if cfg!(miri) { 2.0 } else { 128.0 };
+
+/// The batch widths the main loop is compiled for.
+pub const BATCH_WIDTHS: [usize; 4] = [1, 2, 4, 8];
+pub const DEFAULT_BATCH: usize = 4;
+
+/// Maximum number of numeric fields after the name of each line.
+pub const MAX_FIELDS: usize = 16;
+
+/// Capacity of the per worker map of `--bucket`, the expected number of station and bucket pairs of
+/// each worker, such as 1000 stations over 2 days of hours or 2 months of days. Its keys and
+/// summaries are larger than those of the stations, so the table only has twice as many entries,
+/// about 10 MB.
+const SERIES_CAPACITY: usize = if cfg!(miri) { 500 } else { 50_000 };
+
+/// Options of the fast engine.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    /// Hash function used for the station names
+    pub hash: HashKind,
+    /// `None` to detect the best supported level at runtime
+    pub cpu: Option<CpuLevel>,
+    /// Number of worker threads, `None` to use the available parallelism
+    pub workers: Option<usize>,
+    /// Number of cursors each worker advances together in the main loop, one of [`BATCH_WIDTHS`],
+    /// `None` for [`DEFAULT_BATCH`]
+    pub batch: Option<usize>,
+    /// Print hash map diagnostics to stderr
+    pub stats: bool,
+    /// Print the progress of the parsing to stderr
+    pub progress: bool,
+    /// Only aggregate these stations, the lines of the others being skipped by the main loop. `None`
+    /// for all of them
+    pub stations: Option<Vec<String>>,
+    /// Drop or clamp the measurements out of this range, `None` to keep them all
+    pub range: Option<MeasurementRange>,
+    /// Number of numeric fields after the name of each line, each with its own summary, at most
+    /// [`MAX_FIELDS`]. `None` for 1
+    pub fields: Option<usize>,
+    /// The bytes after the name and the fields, and at the end of the lines
+    pub separators: Separators,
+    /// Read the input as CSV: skip the comment lines (`#`) and the header line, and accept quoted
+    /// names (RFC 4180), which may contain the delimiter and escaped quotes (`""`) but no record
+    /// separator. The lines with a quoted name are parsed outside of the main loop
+    pub csv: bool,
+    /// Aggregate the lines `<name>;<timestamp>;<temperature>\n` per station and bucket of this
+    /// width with [`fast_series_results`], `None` for the other functions
+    pub bucket: Option<BucketWidth>,
+}
+
+/// Compute the result of every station in `file_bytes`, a 1BRC measurements file.
+///
+/// The input must follow the 1BRC format, every line (including the last one) being
+/// `<name>;<temperature>\n`, otherwise the result is unspecified.
+///
+/// The names of the results point into `file_bytes`, or into the station list of `config`, except
+/// the quoted CSV names with escaped quotes.
+pub fn fast_results<'a>(file_bytes: &'a [u8], config: &'a Config) -> Vec<StationResult<'a>> {
+    fast_results_timed(file_bytes, config).0
+}
+
+/// Same as [`fast_results`], also returning the time spent in each phase.
+pub fn fast_results_timed<'a>(
+    file_bytes: &'a [u8],
+    config: &'a Config,
+) -> (Vec<StationResult<'a>>, EngineTimings) {
+    let (mut fields, timings) = fast_field_results_timed(file_bytes, config);
+    assert_eq!(
+        fields.len(),
+        1,
+        "use `fast_field_results` with several fields"
+    );
+    (fields.pop().unwrap(), timings)
+}
+
+/// Compute the results of every field of every station in `file_bytes`, whose lines are
+/// `<name>;<field 1>;...;<field k>\n` with the `k` fields and the separators of `config`, each
+/// field formatted like a 1BRC temperature. One list of results per field, in their order in the
+/// lines.
+pub fn fast_field_results<'a>(
+    file_bytes: &'a [u8],
+    config: &'a Config,
+) -> Vec<Vec<StationResult<'a>>> {
+    fast_field_results_timed(file_bytes, config).0
+}
+
+/// Same as [`fast_field_results`], also returning the time spent in each phase.
+pub fn fast_field_results_timed<'a>(
+    file_bytes: &'a [u8],
+    config: &'a Config,
+) -> (Vec<Vec<StationResult<'a>>>, EngineTimings) {
+    // a single field keeps the main loop of the 1BRC format
+    match (config.hash, config.fields.unwrap_or(1)) {
+        (HashKind::Xor, 1) => fast_results_with::<XorHash, StationSummary>(file_bytes, config),
+        (HashKind::MulRot, 1) => {
+            fast_results_with::<MulRotHash, StationSummary>(file_bytes, config)
+        }
+        (HashKind::Fx, 1) => fast_results_with::<FxHash, StationSummary>(file_bytes, config),
+        (HashKind::Crc32, 1) => fast_results_with::<Crc32Hash, StationSummary>(file_bytes, config),
+        (HashKind::Secure, 1) => {
+            fast_results_with::<SecureHash, StationSummary>(file_bytes, config)
+        }
+        (HashKind::Xor, _) => fast_results_with::<XorHash, FieldSummaries>(file_bytes, config),
+        (HashKind::MulRot, _) => {
+            fast_results_with::<MulRotHash, FieldSummaries>(file_bytes, config)
+        }
+        (HashKind::Fx, _) => fast_results_with::<FxHash, FieldSummaries>(file_bytes, config),
+        (HashKind::Crc32, _) => fast_results_with::<Crc32Hash, FieldSummaries>(file_bytes, config),
+        (HashKind::Secure, _) => {
+            fast_results_with::<SecureHash, FieldSummaries>(file_bytes, config)
+        }
+    }
+}
+
+/// Compute the time series of every station in `file_bytes`, whose lines are
+/// `<name>;<timestamp>;<temperature>\n` with the separators of `config`: the result of each station
+/// over each bucket of `config.bucket` with measurements, in no particular order.
+///
+/// The names and the temperatures are parsed by the kernels of the main loop, and the timestamps one
+/// at a time by [`series::parse_timestamp`].
+pub fn fast_series_results<'a>(file_bytes: &'a [u8], config: &'a Config) -> Vec<SeriesResult<'a>> {
+    match config.hash {
+        HashKind::Xor => series_results_with::<XorHash>(file_bytes, config),
+        HashKind::MulRot => series_results_with::<MulRotHash>(file_bytes, config),
+        HashKind::Fx => series_results_with::<FxHash>(file_bytes, config),
+        HashKind::Crc32 => series_results_with::<Crc32Hash>(file_bytes, config),
+        HashKind::Secure => series_results_with::<SecureHash>(file_bytes, config),
+    }
+}
+
+fn series_results_with<'a, S>(file_bytes: &'a [u8], config: &'a Config) -> Vec<SeriesResult<'a>>
+where
+    S: BuildHasher + Default + Send,
+{
+    let width = config.bucket.expect("a bucket width");
+    let workers_num = config
+        .workers
+        .unwrap_or_else(|| std::thread::available_parallelism().unwrap().get());
+    let cpu = config.cpu.unwrap_or_else(CpuLevel::detect);
+    let batch = config.batch.unwrap_or(DEFAULT_BATCH);
+    let options = ParseOptions {
+        stations: config.stations.as_deref(),
+        range: config.range.as_ref().map(Bounded::new),
+        separators: config.separators,
+        ..ParseOptions::new(cpu, batch)
+    };
+    let record = options.separators.record;
+    let file = file_bytes;
+    let (file_bytes, remainder) = file_bytes.split_at(remainder_idx(file_bytes, record));
+    let mut series = std::thread::scope(|scope| {
+        let workers = split_bytes_aligned(file_bytes, workers_num, record)
+            .into_iter()
+            .map(|part| scope.spawn(move || parse_series_part::<S>(file, part, &options, width)))
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|w| w.join().unwrap())
+            .reduce(|mut series, worker_series| {
+                for (key, summary) in worker_series.iter() {
+                    series.get_or_default(*key).merge(summary);
+                }
+                series
+            })
+            .unwrap()
+    });
+    parse_series_remainder(remainder, &options, width, &mut series);
+    series
+        .iter()
+        // all the measurements dropped
+        .filter(|(_, summary)| summary.count > 0)
+        .map(|(key, summary)| SeriesResult {
+            bucket: key.bucket,
+            result: summary.result(Cow::Borrowed(key.name.to_str())),
+        })
+        .collect()
+}
+
+/// A station and the start of one of its time buckets, the key of [`SeriesMap`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct StationBucket<'a> {
+    name: StationName<'a>,
+    /// The hash of `name` with the hasher of the map, so the key is hashed without the name bytes
+    name_hash: u64,
+    bucket: i64,
+}
+impl<'a> StationBucket<'a> {
+    fn new(name: KeyHashPair<StationName<'a>>, bucket: i64) -> Self {
+        Self {
+            name: name.key,
+            name_hash: name.hash(),
+            bucket,
+        }
+    }
+}
+impl Hash for StationBucket<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // mixed before the hasher, as with a linear one such as `XorHash` the stations and buckets
+        // that differ in the same bits would collide
+        let key = self.name_hash.rotate_left(32) ^ (self.bucket as u64).wrapping_mul(BUCKET_MUL);
+        // the finalizer of splitmix64
+        let key = (key ^ (key >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        let key = (key ^ (key >> 27)).wrapping_mul(0x94d049bb133111eb);
+        state.write_u64(key ^ (key >> 31));
+    }
+}
+const BUCKET_MUL: u64 = 0x9e3779b97f4a7c15;
+
+/// The listed stations of `--stations`, to skip the lines of the others.
+fn listed_stations<'a, S>(stations: &'a [String]) -> HashMap<'a, S, ()>
+where
+    S: BuildHasher + Default,
+{
+    let mut listed = HashMap::new(stations.len().max(1), HASHMAP_SAFETY_FACTOR);
+    for name in stations {
+        listed.get_or_default(StationName::new(name.as_bytes()));
+    }
+    listed
+}
+
+/// Add a measurement to the summary of its station and bucket. With a list of stations, the
+/// measurements of the others are skipped.
+#[inline(always)]
+fn update_series<'a, S>(
+    series: &mut SeriesMap<'a, S>,
+    listed: Option<&mut HashMap<'a, S, ()>>,
+    name: KeyHashPair<StationName<'a>>,
+    bucket: i64,
+    measurement: i16,
+    range: Option<Bounded>,
+) where
+    S: BuildHasher,
+{
+    if let Some(listed) = listed
+        && listed.get_mut(name).is_none()
+    {
+        return;
+    }
+    series
+        .get_or_default(StationBucket::new(name, bucket))
+        .update(measurement, range);
+}
+
+/// Parse a part with the series loop for the batch width of `options`, like [`parse_part_batch`].
+fn parse_series_part<'a, S>(
+    file: &'a [u8],
+    part: &'a [u8],
+    options: &ParseOptions<'a>,
+    width: BucketWidth,
+) -> SeriesMap<'a, S>
+where
+    S: BuildHasher + Default,
+{
+    match options.batch {
+        1 => parse_series_batch::<S, 1>(file, part, options, width),
+        2 => parse_series_batch::<S, 2>(file, part, options, width),
+        4 => parse_series_batch::<S, 4>(file, part, options, width),
+        8 => parse_series_batch::<S, 8>(file, part, options, width),
+        batch => panic!("unsupported batch width {batch}"),
+    }
+}
+
+/// Run the series loop of the CPU level of `options`, with the byte search kernels of the main loop
+/// variant of the same level (see [`parse_file_bytes_batch`]).
+fn parse_series_batch<'a, S, const BATCH: usize>(
+    file: &'a [u8],
+    part: &'a [u8],
+    options: &ParseOptions<'a>,
+    width: BucketWidth,
+) -> SeriesMap<'a, S>
+where
+    S: BuildHasher + Default,
+    Batch<BATCH>: DecodeBatch<BATCH>,
+{
+    let cpu = options.cpu;
+    assert!(cpu.is_supported());
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX2 (see [`CpuLevel::Avx2`])
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2,bmi1,bmi2,lzcnt,popcnt")]
+    #[inline(never)]
+    unsafe fn parse_series_avx2<'a, S, const BATCH: usize>(
+        file: &'a [u8],
+        part: &'a [u8],
+        options: &ParseOptions<'a>,
+        width: BucketWidth,
+    ) -> SeriesMap<'a, S>
+    where
+        S: BuildHasher + Default,
+        Batch<BATCH>: DecodeBatch<BATCH>,
+    {
+        parse_series::<S, simd::Avx2, BATCH>(file, part, options, width)
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX-512 (see [`CpuLevel::Avx512`])
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2,bmi1,bmi2,lzcnt,popcnt,avx512f,avx512bw,avx512vl")]
+    #[inline(never)]
+    unsafe fn parse_series_avx512<'a, S, const BATCH: usize>(
+        file: &'a [u8],
+        part: &'a [u8],
+        options: &ParseOptions<'a>,
+        width: BucketWidth,
+    ) -> SeriesMap<'a, S>
+    where
+        S: BuildHasher + Default,
+        Batch<BATCH>: DecodeBatch<BATCH>,
+    {
+        parse_series::<S, simd::Avx512, BATCH>(file, part, options, width)
+    }
+
+    match cpu {
+        CpuLevel::Baseline => parse_series::<S, DefaultSearch, BATCH>(file, part, options, width),
+        #[cfg(target_arch = "x86_64")]
+        CpuLevel::Avx2 => unsafe { parse_series_avx2::<S, BATCH>(file, part, options, width) },
+        #[cfg(target_arch = "x86_64")]
+        CpuLevel::Avx512 => unsafe { parse_series_avx512::<S, BATCH>(file, part, options, width) },
+        #[cfg(not(target_arch = "x86_64"))]
+        CpuLevel::Avx2 | CpuLevel::Avx512 => unreachable!(),
+    }
+}
+
+/// The summaries per station and bucket of a part of the file, with a batch of cursors like the main
+/// loop: the names are parsed and hashed by [`StationName::parse_and_hash`] and the temperatures of
+/// all cursors decoded at once, only the timestamps being parsed one at a time.
+///
+/// `part` is a part of `file` that ends at least `READ_AHEAD` bytes before its end, as in
+/// [`parse_file_bytes`].
+///
+/// Always inlined into one of the variants of [`parse_series_batch`], like the main loop.
+#[inline(always)]
+fn parse_series<'a, S, B, const BATCH: usize>(
+    file: &'a [u8],
+    part: &'a [u8],
+    options: &ParseOptions<'a>,
+    width: BucketWidth,
+) -> SeriesMap<'a, S>
+where
+    S: BuildHasher + Default,
+    B: ByteSearch,
+    Batch<BATCH>: DecodeBatch<BATCH>,
+{
+    macro_rules! batch {
+        ($f:expr) => {
+            std::array::from_fn::<_, BATCH, _>($f)
+        };
+    }
+
+    let Separators { delimiter, record } = options.separators;
+    let mut listed = options.stations.map(listed_stations::<S>);
+    let mut series = SeriesMap::new(SERIES_CAPACITY, 2.0);
+    let (mut file_ptr, file_end) = {
+        let splits = split_bytes_aligned(part, BATCH, record);
+        let file_ptr = batch!(|bi| file.as_ptr().with_addr(splits[bi].as_ptr().addr()));
+        let file_end = batch!(|bi| unsafe { file_ptr[bi].add(splits[bi].len()) });
+        (file_ptr, file_end)
+    };
+
+    while hint::likely((0..BATCH).all(|bi| file_ptr[bi] < file_end[bi])) {
+        let first_word = batch!(|bi| unsafe { read::<u128>(file, file_ptr[bi]) });
+        let name = batch!(|bi| unsafe {
+            StationName::parse_and_hash::<B, NAME_STEP_WORDS>(
+                file,
+                &mut file_ptr[bi],
+                first_word[bi],
+                delimiter,
+                series.hasher(),
+            )
+        });
+        let bucket =
+            batch!(|bi| width.bucket(parse_timestamp_at(file, &mut file_ptr[bi], delimiter)));
+        let temperature_word = batch!(|bi| unsafe { read::<u64>(file, file_ptr[bi]) });
+        let (measurement, temperature_len) = Batch::<BATCH>::decode(temperature_word);
+        batch!(|bi| file_ptr[bi] = unsafe { file_ptr[bi].add(temperature_len[bi]) });
+        batch!(|bi| {
+            let (name, bucket, measurement) = (name[bi], bucket[bi], measurement[bi]);
+            update_series(
+                &mut series,
+                listed.as_mut(),
+                name,
+                bucket,
+                measurement,
+                options.range,
+            );
+        });
+    }
+
+    // the remaining lines of each cursor, one at a time
+    batch!(|bi| {
+        let (mut file_ptr, file_end) = (file_ptr[bi], file_end[bi]);
+        while hint::likely(file_ptr < file_end) {
+            let first_word = unsafe { read::<u128>(file, file_ptr) };
+            let name = unsafe {
+                StationName::parse_and_hash::<B, NAME_STEP_WORDS>(
+                    file,
+                    &mut file_ptr,
+                    first_word,
+                    delimiter,
+                    series.hasher(),
+                )
+            };
+            let bucket = width.bucket(parse_timestamp_at(file, &mut file_ptr, delimiter));
+            let measurement = unsafe { parse_temperature::<B>(file, &mut file_ptr, record) };
+            update_series(
+                &mut series,
+                listed.as_mut(),
+                name,
+                bucket,
+                measurement,
+                options.range,
+            );
+        }
+    });
+    series
+}
+
+/// Parse the timestamp at `file_ptr`, a pointer into `file` followed by `delimiter`, and move
+/// `file_ptr` past the delimiter. The timestamps have no fixed layout for the kernels, so they are
+/// found and parsed by scalar code.
+#[inline(always)]
+fn parse_timestamp_at(file: &[u8], file_ptr: &mut *const u8, delimiter: u8) -> i64 {
+    let rest = &file[file_ptr.addr() - file.as_ptr().addr()..];
+    let len = rest
+        .iter()
+        .position(|&b| b == delimiter)
+        .expect("expected <name>;<timestamp>;<temperature>");
+    *file_ptr = file_ptr.wrapping_add(len + 1);
+    parse_timestamp(&rest[..len])
+}
+
+/// [`series::parse_timestamp`], panicking on an invalid timestamp.
+#[inline(always)]
+fn parse_timestamp(timestamp: &[u8]) -> i64 {
+    series::parse_timestamp(timestamp)
+        .unwrap_or_else(|| panic!("invalid timestamp '{}'", String::from_utf8_lossy(timestamp)))
+}
+
+/// The lines of `--bucket` within `READ_AHEAD` bytes of the end of the file, with bounds checks.
+fn parse_series_remainder<'a, S>(
+    remainder: &'a [u8],
+    options: &ParseOptions<'a>,
+    width: BucketWidth,
+    series: &mut SeriesMap<'a, S>,
+) where
+    S: BuildHasher + Default,
+{
+    let Separators { delimiter, record } = options.separators;
+    let mut listed = options.stations.map(listed_stations::<S>);
+    for line in remainder.split_inclusive(|&b| b == record) {
+        let line = line.strip_suffix(&[record]).unwrap();
+        let mut parts = line.splitn(3, |&b| b == delimiter);
+        let (Some(name), Some(timestamp), Some(temperature)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            panic!("expected <name>;<timestamp>;<temperature>");
+        };
+        let name = StationName::new(name);
+        let name = unsafe { KeyHashPair::new_unchecked(name, series.hasher().hash_one(name)) };
+        let bucket = width.bucket(parse_timestamp(timestamp));
+        if !format::is_measurement(temperature) {
+            panic!(
+                "invalid temperature '{}'",
+                String::from_utf8_lossy(temperature)
+            );
+        }
+        // decoded like the temperatures of the main loop, from a copy followed by the separator
+        let mut word = [record; 8];
+        word[..temperature.len()].copy_from_slice(temperature);
+        let ([measurement], _) = Batch::<1>::decode([u64::from_le_bytes(word)]);
+        update_series(
+            series,
+            listed.as_mut(),
+            name,
+            bucket,
+            measurement,
+            options.range,
+        );
+    }
+}
+
+fn fast_results_with<'a, S, V>(
+    file_bytes: &'a [u8],
+    config: &'a Config,
+) -> (Vec<Vec<StationResult<'a>>>, EngineTimings)
+where
+    S: BuildHasher + Default + Send,
+    V: Summaries,
+{
+    let fields = config.fields.unwrap_or(1);
+    assert!(
+        (1..=MAX_FIELDS).contains(&fields),
+        "unsupported number of fields {fields}"
+    );
+    let cpu = config.cpu.unwrap_or_else(CpuLevel::detect);
+    let workers_num = config
+        .workers
+        .unwrap_or_else(|| std::thread::available_parallelism().unwrap().get());
+    let batch = config.batch.unwrap_or(DEFAULT_BATCH);
+    let mut timings = EngineTimings::default();
+    let options = ParseOptions {
+        stations: config.stations.as_deref(),
+        range: config.range.as_ref().map(Bounded::new),
+        fields,
+        separators: config.separators,
+        csv: config.csv,
+        ..ParseOptions::new(cpu, batch)
+    };
+    let file_bytes = if config.csv {
+        &file_bytes[format::csv_preamble_len(file_bytes, config.separators)..]
+    } else {
+        file_bytes
+    };
+    let (measurements, worker_stats) = aggregate::<S, V>(
+        file_bytes,
+        workers_num,
+        options,
+        config.progress,
+        &mut timings,
+    );
+
+    if config.stats {
+        let collisions = measurements
+            .collisions()
+            .map(|(name, table_name)| (name.to_str(), table_name.map(|n| n.to_str())))
+            .collect::<Vec<_>>();
+        // the merged map is the map of the first worker, whose own hits are already in its row
+        let merged = measurements.stats();
+        let merged = HashMapStats {
+            fallback_hits: merged.fallback_hits - worker_stats[0].fallback_hits,
+            ..merged
+        };
+        stats::print_hashmap_stats(&worker_stats, merged, &collisions);
+    }
+
+    let stopwatch = Stopwatch::start();
+    // the listed stations are the keys of their quoted names
+    let quoted_keys = config.csv && config.stations.is_none();
+    let escaped = if quoted_keys {
+        merge_escaped_names(&measurements)
+    } else {
+        std::collections::HashMap::new()
+    };
+    let stations = measurements
+        .iter()
+        .map(|(name, summaries)| (name.to_str(), summaries))
+        .filter(|(name, _)| !quoted_keys || !(name.starts_with('"') || escaped.contains_key(*name)))
+        .map(|(name, summaries)| (Cow::Borrowed(name), summaries))
+        .chain(
+            escaped
+                .iter()
+                .map(|(name, summaries)| (name.clone(), summaries)),
+        )
+        .collect::<Vec<_>>();
+    let results = (0..fields)
+        .map(|field| {
+            stations
+                .iter()
+                .filter_map(|(name, summaries)| {
+                    summaries
+                        .fields()
+                        .get(field)
+                        // the listed stations missing from the file, or with all their
+                        // measurements dropped
+                        .filter(|summary| summary.count > 0)
+                        .map(|summary| summary.result(name.clone()))
+                })
+                .collect()
+        })
+        .collect();
+    timings.results = stopwatch.stop();
+    (results, timings)
+}
+
+/// The summaries of the `--csv` stations whose names have escaped quotes, by their unescaped names:
+/// merged from their quoted lines, keyed by the escaped name (see [`parse_csv_line`]), and from the
+/// lines where the same name isn't quoted, keyed by itself.
+fn merge_escaped_names<'a, S, V>(
+    measurements: &HashMap<'a, S, V>,
+) -> std::collections::HashMap<Cow<'a, str>, V>
+where
+    V: Summaries,
+{
+    let mut escaped = std::collections::HashMap::<Cow<str>, V>::new();
+    for (name, summaries) in measurements.iter() {
+        let name = name.to_str();
+        if name.starts_with('"') {
+            escaped
+                .entry(format::csv_name(name))
+                .or_default()
+                .merge(summaries);
+        }
+    }
+    for (name, summaries) in measurements.iter() {
+        let name = name.to_str();
+        if !name.starts_with('"')
+            && let Some(merged) = escaped.get_mut(name)
+        {
+            merged.merge(summaries);
+        }
+    }
+    escaped
+}
+
+/// How the workers parse their part of the file.
+#[derive(Clone, Copy)]
+struct ParseOptions<'a> {
+    cpu: CpuLevel,
+    /// One of [`BATCH_WIDTHS`]
+    batch: usize,
+    /// Only aggregate these stations. The listed stations are all in the map, even those missing
+    /// from the file, with a count of 0
+    stations: Option<&'a [String]>,
+    /// Drop or clamp the measurements out of this range
+    range: Option<Bounded>,
+    /// Number of numeric fields of the lines
+    fields: usize,
+    separators: Separators,
+    /// Lines with a quoted name or a comment, see [`parse_csv_line`]
+    csv: bool,
+}
+impl ParseOptions<'_> {
+    /// All the stations and all the measurements, of a single field, in the 1BRC format.
+    fn new(cpu: CpuLevel, batch: usize) -> Self {
+        Self {
+            cpu,
+            batch,
+            stations: None,
+            range: None,
+            fields: 1,
+            separators: Separators::default(),
+            csv: false,
+        }
+    }
+}
+
+/// Compute the summaries of the stations in the file, using `workers_num` threads.
+/// Also returns the hash map stats of each worker.
+fn aggregate<'a, S, V>(
+    file_bytes: &'a [u8],
+    workers_num: usize,
+    options: ParseOptions<'a>,
+    progress: bool,
+    timings: &mut EngineTimings,
+) -> (HashMap<'a, S, V>, Vec<HashMapStats>)
+where
+    S: BuildHasher + Default + Send,
+    V: Summaries,
+{
+    let Separators { delimiter, record } = options.separators;
+    // sometimes we read READ_AHEAD (128) bytes ahead, without checking if we reached EOF.
+    // to avoid reading past EOF, we find the last newline before the last READ_AHEAD bytes,
+    // and split the file there. The main loop will process the first part without bounds checks,
+    // and the second part (the "remainder") with bounds checks.
+    let remainder_idx = remainder_idx(file_bytes, record);
+    let file = file_bytes;
+    let (file_bytes, mut file_bytes_remainder) = file_bytes.split_at(remainder_idx);
+
+    let progress = progress.then(|| Progress::new(file, workers_num, record));
+    let (mut measurements, worker_stats) = std::thread::scope(|scope| {
+        let stopwatch = Stopwatch::start();
+        // Split the file into chunks for each worker
+        let file_bytes = split_bytes_aligned(file_bytes, workers_num, record);
+
+        // Spawn worker threads
+        let workers = file_bytes
+            .into_iter()
+            .enumerate()
+            .map(|(i, file_bytes)| {
+                let progress = progress.as_ref().map(|progress| progress.worker(i));
+                scope.spawn(move || {
+                    let stopwatch = Stopwatch::start();
+                    let measurements =
+                        parse_file_bytes::<S, V>(file, file_bytes, options, progress);
+                    (measurements, file_bytes.len(), stopwatch.stop())
+                })
+            })
+            .collect::<Vec<_>>();
+        // the reporter stops when `done` is dropped, after the workers
+        let done = progress.as_ref().map(|progress| {
+            let (done, receiver) = std::sync::mpsc::channel();
+            scope.spawn(move || progress.report(receiver));
+            done
+        });
+
+        let workers = workers
+            .into_iter()
+            .map(|w| w.join().unwrap())
+            .collect::<Vec<_>>();
+        drop(done);
+        timings.parse = Timing {
+            wall: stopwatch.stop().wall,
+            cpu: workers.iter().map(|(_, _, timing)| timing.cpu).sum(),
+        };
+        timings.workers = workers
+            .iter()
+            .map(|(measurements, bytes, timing)| WorkerTimings {
+                bytes: *bytes,
+                rows: measurements
+                    .iter()
+                    .map(|(_, s)| s.rows(options.range))
+                    .sum(),
+                parse: *timing,
+            })
+            .collect();
+        let workers = workers
+            .into_iter()
+            .map(|(measurements, _, _)| measurements)
+            .collect::<Vec<_>>();
+        let worker_stats = workers.iter().map(|w| w.stats()).collect::<Vec<_>>();
+
+        // Merge results
+        let stopwatch = Stopwatch::start();
+        let measurements = workers
+            .into_iter()
+            .reduce(|mut measurements, worker_measurements| {
+                for (station_name, summaries) in worker_measurements.iter() {
+                    measurements.get_or_default(*station_name).merge(summaries);
+                }
+                measurements
+            });
+        timings.merge = stopwatch.stop();
+        (measurements.unwrap(), worker_stats)
+    });
+
+    // process remainder (trivially, no optimizations)
+    let stopwatch = Stopwatch::start();
+    timings.remainder_bytes = file_bytes_remainder.len();
+    while !file_bytes_remainder.is_empty() {
+        timings.remainder_rows += 1;
+        if options.csv && format::is_csv_special(file_bytes_remainder[0]) {
+            let line_start = file.len() - file_bytes_remainder.len();
+            let len = parse_csv_line(file, line_start, &options, options.range, &mut measurements);
+            file_bytes_remainder = &file_bytes_remainder[len..];
+            continue;
+        }
+        let newline_pos = file_bytes_remainder
+            .iter()
+            .position(|&b| b == record)
+            .unwrap();
+        let line = &file_bytes_remainder[..newline_pos];
+        file_bytes_remainder = &file_bytes_remainder[newline_pos + 1..]; // skip newline
+
+        let semicolon_pos = line.iter().position(|&b| b == delimiter).unwrap();
+        let name_bytes = &line[..semicolon_pos];
+        let station_name = StationName::new(name_bytes);
+        let (fields, fields_num) = parse_measurements(&line[semicolon_pos + 1..], delimiter);
+
+        update_summary(
+            &mut measurements,
+            station_name,
+            options.stations.is_some(),
+            |summaries| summaries.update_line(&fields[..fields_num], options.range),
+        );
+    }
+    timings.remainder = stopwatch.stop();
+
+    (measurements, worker_stats)
+}
+
+/// The start of the lines within `READ_AHEAD` bytes of the end of `file_bytes`, the remainder that
+/// the main loop can't parse without bounds checks.
+fn remainder_idx(file_bytes: &[u8], record: u8) -> usize {
+    match file_bytes.len().checked_sub(READ_AHEAD) {
+        Some(idx) => file_bytes[..idx]
+            .iter()
+            .rposition(|&b| b == record)
+            .map_or(0, |newline_pos| newline_pos + 1),
+        None => 0, // the whole file is the remainder
+    }
+}
+
+/// Parse the file bytes with the main loop compiled for the CPU level and batch width of `options`.
+///
+/// `file_bytes` is a part of `file` that ends at least `READ_AHEAD` bytes before its end, as the
+/// main loop reads past the end of the part.
+///
+/// With a list of stations, the map is seeded with them, and the lines of the other stations are
+/// skipped. With a range, the measurements out of it are dropped or clamped.
+///
+/// With a `progress` counter, the part is parsed in chunks of [`progress::CHUNK_SIZE`], adding the
+/// size of each chunk to the counter after it.
+fn parse_file_bytes<'a, S, V>(
+    file: &'a [u8],
+    file_bytes: &'a [u8],
+    options: ParseOptions<'a>,
+    progress: Option<&AtomicUsize>,
+) -> HashMap<'a, S, V>
+where
+    S: BuildHasher + Default,
+    V: Summaries,
+{
+    let stations = options.stations;
+    let capacity = stations.map_or(0, <[_]>::len).max(1000);
+    let mut measurements = HashMap::new(capacity, HASHMAP_SAFETY_FACTOR);
+    for name in stations.into_iter().flatten() {
+        measurements.get_or_default(StationName::new(name.as_bytes()));
+    }
+    let mut parse = |part: &'a [u8]| match options.range {
+        None => V::parse_part(file, part, &options, Unbounded, &mut measurements),
+        Some(range) => V::parse_part(file, part, &options, range, &mut measurements),
+    };
+    match progress {
+        None => parse(file_bytes),
+        Some(progress) => {
+            let chunks_num = file_bytes.len().div_ceil(progress::CHUNK_SIZE).max(1);
+            for chunk in split_bytes_aligned(file_bytes, chunks_num, options.separators.record) {
+                parse(chunk);
+                progress.fetch_add(chunk.len(), Ordering::Relaxed);
+            }
+        }
+    }
+    measurements
+}
+
+/// Parse a part with the main loop for `batch`, `listed` and `csv`, each combination being
+/// compiled separately for the policy `P`.
+fn parse_part<'a, S, P>(
+    file: &'a [u8],
+    part: &'a [u8],
+    options: &ParseOptions<'a>,
+    policy: P,
+    measurements: &mut HashMap<'a, S>,
+) where
+    S: BuildHasher + Default,
+    P: RangePolicy,
+{
+    let m = measurements;
+    match (options.stations.is_some(), options.csv) {
+        (false, false) => parse_part_batch::<S, P, false, false>(file, part, options, policy, m),
+        (true, false) => parse_part_batch::<S, P, true, false>(file, part, options, policy, m),
+        (false, true) => parse_part_batch::<S, P, false, true>(file, part, options, policy, m),
+        (true, true) => parse_part_batch::<S, P, true, true>(file, part, options, policy, m),
+    }
+}
+
+fn parse_part_batch<'a, S, P, const LISTED: bool, const CSV: bool>(
+    file: &'a [u8],
+    part: &'a [u8],
+    options: &ParseOptions<'a>,
+    policy: P,
+    m: &mut HashMap<'a, S>,
+) where
+    S: BuildHasher + Default,
+    P: RangePolicy,
+{
+    match options.batch {
+        1 => parse_file_bytes_batch::<S, P, 1, LISTED, CSV>(file, part, options, policy, m),
+        2 => parse_file_bytes_batch::<S, P, 2, LISTED, CSV>(file, part, options, policy, m),
+        4 => parse_file_bytes_batch::<S, P, 4, LISTED, CSV>(file, part, options, policy, m),
+        8 => parse_file_bytes_batch::<S, P, 8, LISTED, CSV>(file, part, options, policy, m),
+        batch => panic!("unsupported batch width {batch}"),
+    }
+}
+
+/// Run the main loop of the CPU level of `options`: the baseline with [`DefaultSearch`], or the
+/// AVX2 and AVX-512 variants with their own byte search kernels, [`simd::Avx2`] and [`simd::Avx512`].
+fn parse_file_bytes_batch<'a, S, P, const BATCH: usize, const LISTED: bool, const CSV: bool>(
+    file: &'a [u8],
+    file_bytes: &'a [u8],
+    options: &ParseOptions<'a>,
+    policy: P,
+    measurements: &mut HashMap<'a, S>,
+) where
+    S: BuildHasher + Default,
+    P: RangePolicy,
+    Batch<BATCH>: DecodeBatch<BATCH>,
+{
+    let cpu = options.cpu;
+    assert!(cpu.is_supported());
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX2 (see [`CpuLevel::Avx2`])
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2,bmi1,bmi2,lzcnt,popcnt")]
+    #[inline(never)]
+    unsafe fn parse_file_bytes_avx2<
+        'a,
+        S,
+        P,
+        const BATCH: usize,
+        const LISTED: bool,
+        const CSV: bool,
+    >(
+        file: &'a [u8],
+        file_bytes: &'a [u8],
+        options: &ParseOptions<'a>,
+        policy: P,
+        measurements: &mut HashMap<'a, S>,
+    ) where
+        S: BuildHasher + Default,
+        P: RangePolicy,
+        Batch<BATCH>: DecodeBatch<BATCH>,
+    {
+        parse_file_bytes_impl::<S, P, simd::Avx2, BATCH, LISTED, CSV>(
+            file,
+            file_bytes,
+            options,
+            policy,
+            measurements,
+        )
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX-512 (see [`CpuLevel::Avx512`])
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2,bmi1,bmi2,lzcnt,popcnt,avx512f,avx512bw,avx512vl")]
+    #[inline(never)]
+    unsafe fn parse_file_bytes_avx512<
+        'a,
+        S,
+        P,
+        const BATCH: usize,
+        const LISTED: bool,
+        const CSV: bool,
+    >(
+        file: &'a [u8],
+        file_bytes: &'a [u8],
+        options: &ParseOptions<'a>,
+        policy: P,
+        measurements: &mut HashMap<'a, S>,
+    ) where
+        S: BuildHasher + Default,
+        P: RangePolicy,
+        Batch<BATCH>: DecodeBatch<BATCH>,
+    {
+        parse_file_bytes_impl::<S, P, simd::Avx512, BATCH, LISTED, CSV>(
+            file,
+            file_bytes,
+            options,
+            policy,
+            measurements,
+        )
+    }
+
+    #[inline(never)]
+    fn parse_file_bytes_baseline<
+        'a,
+        S,
+        P,
+        const BATCH: usize,
+        const LISTED: bool,
+        const CSV: bool,
+    >(
+        file: &'a [u8],
+        file_bytes: &'a [u8],
+        options: &ParseOptions<'a>,
+        policy: P,
+        measurements: &mut HashMap<'a, S>,
+    ) where
+        S: BuildHasher + Default,
+        P: RangePolicy,
+        Batch<BATCH>: DecodeBatch<BATCH>,
+    {
+        parse_file_bytes_impl::<S, P, DefaultSearch, BATCH, LISTED, CSV>(
+            file,
+            file_bytes,
+            options,
+            policy,
+            measurements,
+        )
+    }
+
+    match cpu {
+        CpuLevel::Baseline => parse_file_bytes_baseline::<S, P, BATCH, LISTED, CSV>(
+            file,
+            file_bytes,
+            options,
+            policy,
+            measurements,
+        ),
+        #[cfg(target_arch = "x86_64")]
+        CpuLevel::Avx2 => unsafe {
+            parse_file_bytes_avx2::<S, P, BATCH, LISTED, CSV>(
+                file,
+                file_bytes,
+                options,
+                policy,
+                measurements,
+            )
+        },
+        #[cfg(target_arch = "x86_64")]
+        CpuLevel::Avx512 => unsafe {
+            parse_file_bytes_avx512::<S, P, BATCH, LISTED, CSV>(
+                file,
+                file_bytes,
+                options,
+                policy,
+                measurements,
+            )
+        },
+        #[cfg(not(target_arch = "x86_64"))]
+        CpuLevel::Avx2 | CpuLevel::Avx512 => unreachable!(),
+    }
+}
+
+// Always inlined into one of the `parse_file_bytes` variants, so the whole main loop, including the
+// parsing kernels and the hash map operations, is compiled with the variant target features.
+#[inline(always)]
+fn parse_file_bytes_impl<'a, S, P, B, const BATCH: usize, const LISTED: bool, const CSV: bool>(
+    file: &'a [u8],
+    file_bytes: &'a [u8],
+    options: &ParseOptions<'a>,
+    policy: P,
+    measurements: &mut HashMap<'a, S>,
+) where
+    S: BuildHasher + Default,
+    P: RangePolicy,
+    B: ByteSearch,
+    Batch<BATCH>: DecodeBatch<BATCH>,
+{
+    let separators = options.separators;
+    // To utilize the CPU pipeline better, we maintain a batch of cursors into the file,
+    // and process them in parallel (in the same thread).
+    // Every variable that you expected to be u32, is now [u32; BATCH].
+    macro_rules! batch {
+        ($f:expr) => {
+            std::array::from_fn::<_, BATCH, _>($f)
+        };
+    }
+
+    // Split the file into BATCH parts
+    let (mut file_ptr, file_end) = {
+        let splits = split_bytes_aligned(file_bytes, BATCH, separators.record);
+        // the cursors keep the provenance of the whole file, as they read past the end of their split
+        let file_ptr = batch!(|bi| file.as_ptr().with_addr(splits[bi].as_ptr().addr()));
+        let file_end = batch!(|bi| unsafe { file_ptr[bi].add(splits[bi].len()) });
+        (file_ptr, file_end)
+    };
+
+    // Main loop
+    while hint::likely((0..BATCH).all(|bi| file_ptr[bi] < file_end[bi])) {
+        // format: <string: station name>;<double: measurement>
+
+        // Read the name of the station
+        let first_word = batch!(|bi| unsafe { read::<u128>(file, file_ptr[bi]) });
+        if CSV && hint::unlikely((0..BATCH).any(|bi| format::is_csv_special(first_word[bi] as u8)))
+        {
+            // only the cursors at such a line move, then all the cursors are checked again
+            batch!(|bi| if format::is_csv_special(first_word[bi] as u8) {
+                let line_start = file_ptr[bi].addr() - file.as_ptr().addr();
+                let len = parse_csv_line(file, line_start, options, policy, measurements);
+                file_ptr[bi] = unsafe { file_ptr[bi].add(len) };
+            });
+            continue;
+        }
+        let station_name = batch!(|bi| unsafe {
+            StationName::parse_and_hash::<B, NAME_STEP_WORDS>(
+                file,
+                &mut file_ptr[bi],
+                first_word[bi],
+                separators.delimiter,
+                measurements.hasher(),
+            )
+        });
+
+        // Read the temperature measurements of all cursors at once
+        let temperature_word = batch!(|bi| unsafe { read::<u64>(file, file_ptr[bi]) });
+        let (measurement, temperature_len) = Batch::<BATCH>::decode(temperature_word);
+        #[cfg(debug_assertions)]
+        batch!(|bi| {
+            let mut ptr = file_ptr[bi];
+            debug_assert_eq!(measurement[bi], unsafe {
+                parse_temperature::<B>(file, &mut ptr, separators.record)
+            });
+            debug_assert_eq!(ptr, unsafe { file_ptr[bi].add(temperature_len[bi]) });
+        });
+        batch!(|bi| file_ptr[bi] = unsafe { file_ptr[bi].add(temperature_len[bi]) });
+
+        // Update per-station summary
+        batch!(|bi| {
+            update_summary(measurements, station_name[bi], LISTED, |summary| {
+                summary.update(measurement[bi], policy)
+            });
+        });
+    }
+
+    // Process remaining bytes in each batch cursor
+    batch!(|bi| {
+        // same implementation as the main loop, but for a single cursor instead of BATCH
+
+        let (mut file_ptr, file_end) = (file_ptr[bi], file_end[bi]);
+        while hint::likely(file_ptr < file_end) {
+            let first_word = unsafe { read::<u128>(file, file_ptr) };
+            if CSV && hint::unlikely(format::is_csv_special(first_word as u8)) {
+                let line_start = file_ptr.addr() - file.as_ptr().addr();
+                let len = parse_csv_line(file, line_start, options, policy, measurements);
+                file_ptr = unsafe { file_ptr.add(len) };
+                continue;
+            }
+            let station_name = unsafe {
+                StationName::parse_and_hash::<B, NAME_STEP_WORDS>(
+                    file,
+                    &mut file_ptr,
+                    first_word,
+                    separators.delimiter,
+                    measurements.hasher(),
+                )
+            };
+            let measurement =
+                unsafe { parse_temperature::<B>(file, &mut file_ptr, separators.record) };
+            update_summary(measurements, station_name, LISTED, |summary| {
+                summary.update(measurement, policy)
+            });
+        }
+    });
+}
+
+/// The main loop of the lines with several fields, `<name>;<field 1>;...;<field k>\n`, with a
+/// single cursor: the lines are longer, and their fields are parsed one after the other.
+#[inline(never)]
+fn parse_fields<'a, S, P, B>(
+    file: &'a [u8],
+    file_bytes: &'a [u8],
+    options: &ParseOptions<'a>,
+    policy: P,
+    measurements: &mut HashMap<'a, S, FieldSummaries>,
+) where
+    S: BuildHasher + Default,
+    P: RangePolicy,
+    B: ByteSearch,
+{
+    let ParseOptions {
+        fields, separators, ..
+    } = *options;
+    let listed = options.stations.is_some();
+    assert!((1..=MAX_FIELDS).contains(&fields));
+    // the cursor keeps the provenance of the whole file, as it reads past the end of the part
+    let mut file_ptr = file.as_ptr().with_addr(file_bytes.as_ptr().addr());
+    let file_end = unsafe { file_ptr.add(file_bytes.len()) };
+    let mut line = [0_i16; MAX_FIELDS];
+    while file_ptr < file_end {
+        let first_word = unsafe { read::<u128>(file, file_ptr) };
+        if options.csv && format::is_csv_special(first_word as u8) {
+            let line_start = file_ptr.addr() - file.as_ptr().addr();
+            let len = parse_csv_line(file, line_start, options, policy, measurements);
+            file_ptr = unsafe { file_ptr.add(len) };
+            continue;
+        }
+        let station_name = unsafe {
+            StationName::parse_and_hash::<B, NAME_STEP_WORDS>(
+                file,
+                &mut file_ptr,
+                first_word,
+                separators.delimiter,
+                measurements.hasher(),
+            )
+        };
+        for (i, measurement) in line[..fields].iter_mut().enumerate() {
+            let end = if i + 1 < fields {
+                separators.delimiter
+            } else {
+                separators.record
+            };
+            *measurement = unsafe { parse_temperature::<B>(file, &mut file_ptr, end) };
+        }
+        update_summary(measurements, station_name, listed, |summaries| {
+            summaries.update_line(&line[..fields], policy)
+        });
+    }
+}
+
+/// The slow path of the `--csv` lines starting with a comment or a quoted name, at `line_start` in
+/// the file. Returns the length of the line, including its record separator.
+///
+/// The names with escaped quotes stay escaped in the map, keyed by their bytes from the opening quote
+/// so they can't be mistaken for unquoted names, and are unescaped and merged with the lines of the
+/// same names without quotes with the results (see [`merge_escaped_names`]). With a list of stations
+/// they are looked up in it instead, as the listed names are the keys.
+#[cold]
+#[inline(never)]
+fn parse_csv_line<'a, S, V>(
+    file: &'a [u8],
+    line_start: usize,
+    options: &ParseOptions<'a>,
+    policy: impl RangePolicy,
+    measurements: &mut HashMap<'a, S, V>,
+) -> usize
+where
+    S: BuildHasher,
+    V: Summaries,
+{
+    let Separators { delimiter, record } = options.separators;
+    let rest = &file[line_start..];
+    let line = &rest[..rest.iter().position(|&b| b == record).unwrap()];
+    if line[0] == b'#' {
+        return line.len() + 1;
+    }
+    let (name, escaped, fields) = format::split_quoted(line, delimiter);
+    let name = match options.stations {
+        Some(stations) if escaped => {
+            let unescaped = format::unescape(str::from_utf8(name).unwrap());
+            match stations.iter().find(|station| **station == unescaped) {
+                Some(station) => station.as_bytes(),
+                None => return line.len() + 1,
+            }
+        }
+        // with the opening quote
+        None if escaped => &line[..name.len() + 1],
+        _ => name,
+    };
+    let (fields, fields_num) = parse_measurements(fields, delimiter);
+    update_summary(
+        measurements,
+        StationName::new(name),
+        options.stations.is_some(),
+        |summaries| summaries.update_line(&fields[..fields_num], policy),
+    );
+    line.len() + 1
+}
+
+/// The measurements of a line after its name, outside of the main loop.
+fn parse_measurements(bytes: &[u8], delimiter: u8) -> ([i16; MAX_FIELDS], usize) {
+    let mut fields = [0_i16; MAX_FIELDS];
+    let mut fields_num = 0;
+    for measurement_bytes in bytes.split(|&b| b == delimiter) {
+        let measurement = std::str::from_utf8(measurement_bytes)
+            .unwrap()
+            .parse::<f64>()
+            .unwrap();
+        fields[fields_num] = (measurement * 10.0) as i16;
+        fields_num += 1;
+    }
+    (fields, fields_num)
+}
+
+/// Update the summaries of a station. With `listed`, the map was seeded with the listed stations,
+/// and the measurements of the others are skipped.
+///
+/// Always inlined, the main loop passes a constant `listed`.
+#[inline(always)]
+fn update_summary<'a, S, V>(
+    measurements: &mut HashMap<'a, S, V>,
+    station_name: impl Key<StationName<'a>>,
+    listed: bool,
+    update: impl FnOnce(&mut V),
+) where
+    S: BuildHasher,
+    V: Default,
+{
+    if listed {
+        if let Some(summaries) = measurements.get_mut(station_name) {
+            update(summaries);
+        }
+    } else {
+        update(measurements.get_or_default(station_name));
+    }
+}
+
+#[derive(Clone, Copy)]
+struct StationName<'a> {
+    // The first 16 bytes of the name, stored as u128 for fast comparisons and hashing
+    // If the name is shorter than 16 bytes, the upper bytes are zeroed
+    prefix: u128,
+    // Pointer to the remainder of the name (after the first 16 bytes).
+    // Its valid to dereference the 16 bytes before this pointer, as they are part of the name.
+    // We store the pointer to the remainder instead of the beginning of the name as most of the times
+    // we want to access only the remainder (for equality checks and hashing).
+    remainder_ptr: *const u8,
+    // Length of the remainder (can be negative if the name is shorter than 16 bytes)
+    remainder_len: isize,
+    ph: std::marker::PhantomData<&'a [u8]>,
+}
+impl<'a> StationName<'a> {
+    pub fn new(name_bytes: &'a [u8]) -> Self {
+        let mut prefix_bytes = [0_u8; 16];
+        let prefix_len = name_bytes.len().min(16);
+        prefix_bytes[..prefix_len].copy_from_slice(&name_bytes[..prefix_len]);
+        let prefix = u128::from_ne_bytes(prefix_bytes);
+        Self::new_with_prefix(prefix, name_bytes)
+    }
+
+    pub fn new_with_prefix(prefix: u128, full_name: &'a [u8]) -> Self {
+        Self {
+            prefix,
+            // may point past the end of the file for short names, so it is only ever offset back
+            remainder_ptr: full_name.as_ptr().wrapping_add(16),
+            remainder_len: full_name.len().cast_signed() - 16,
+            ph: std::marker::PhantomData,
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn parse_and_hash<B: ByteSearch, const STEP_WORDS: usize>(
+        file: &'a [u8],
+        file_ptr: &mut *const u8,
+        first_word: u128,
+        delimiter: u8,
+        hash: &impl BuildHasher,
+    ) -> KeyHashPair<Self> {
+        let mut name_prefix = first_word;
+        let name_length;
+        let mut hash = hash.build_hasher();
+
+        let semicolon_pos = B::find_16(name_prefix, delimiter);
+        if semicolon_pos < 16 {
+            // fast path, semicolon is in the first 16 bytes
+
+            name_length = semicolon_pos;
+            // zero the upper bytes of name_prefix
+            name_prefix &= (1_u128.wrapping_shl((name_length * 8) as u32)) - 1;
+            hash.write_u128(name_prefix);
+        } else {
+            // slow path, semicolon is after the first 16 bytes
+
+            hash.write_u128(name_prefix);
+            let mut offset = 16;
+            loop {
+                let words = unsafe { read::<[u64; STEP_WORDS]>(file, file_ptr.add(offset)) };
+                let semicolon_pos = B::find_words(&words, delimiter);
+                if semicolon_pos < STEP_WORDS * 8 {
+                    name_length = offset + semicolon_pos;
+                    for word in &words[..semicolon_pos / 8] {
+                        hash.write_u64(*word);
+                    }
+                    hash.write_u64(
+                        words[semicolon_pos / 8]
+                            & ((1_u64.wrapping_shl(((semicolon_pos % 8) * 8) as u32)) - 1),
+                    );
+                    break;
+                }
+                offset += STEP_WORDS * 8;
+                for word in words {
+                    hash.write_u64(word);
+                }
+            }
+        };
+
+        let full_name = unsafe { read::slice(file, *file_ptr, name_length) };
+        *file_ptr = unsafe { file_ptr.add(name_length + 1) }; // skip semicolon
+
+        let hash = hash.finish();
+        let name = StationName::new_with_prefix(name_prefix, full_name);
+        unsafe { KeyHashPair::new_unchecked(name, hash) }
+    }
+
+    fn remainder(&self) -> &[u8] {
+        debug_assert!(self.remainder_len >= 0);
+        unsafe {
+            std::slice::from_raw_parts(self.remainder_ptr, self.remainder_len.cast_unsigned())
+        }
+    }
+
+    #[inline(never)]
+    #[cold]
+    fn to_str(self) -> &'a str {
+        let len = (self.remainder_len + 16).cast_unsigned();
+        let full_name_ptr = self.remainder_ptr.wrapping_sub(16);
+        let full_name = unsafe { std::slice::from_raw_parts(full_name_ptr, len) };
+        str::from_utf8(full_name).unwrap()
+    }
+}
+impl Hash for StationName<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // Must issue the exact same writes as `parse_and_hash`, so the hash is the same for any hasher:
+        // the prefix as u128, and if the name is at least 16 bytes long, the remainder as u64 words.
+        // The last remainder word is zero padded, and is written even if it contains no bytes.
+        state.write_u128(self.prefix);
+        if self.remainder_len >= 0 {
+            let (words, tail) = self.remainder().as_chunks::<8>();
+            for word in words {
+                state.write_u64(u64::from_ne_bytes(*word));
+            }
+            let mut last_word = [0_u8; 8];
+            last_word[..tail.len()].copy_from_slice(tail);
+            state.write_u64(u64::from_ne_bytes(last_word));
+        }
+    }
+}
+impl PartialEq for StationName<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.prefix != other.prefix {
+            return false;
+        }
+        if self.remainder_len <= 0 && other.remainder_len <= 0 {
+            debug_assert_eq!(self.remainder_len, other.remainder_len);
+            return true; // prefixes are equal, and no remainders
+        }
+        if self.remainder_len != other.remainder_len {
+            return false;
+        }
+        self.remainder() == other.remainder()
+    }
+}
+impl Eq for StationName<'_> {}
+unsafe impl<'a> Send for StationName<'a> {}
+unsafe impl<'a> Sync for StationName<'a> {}
+
+/// The summaries of the measurements of a station, one per field of its lines.
+trait Summaries: Default + Send {
+    /// One per field, none if the station was seeded with `--stations` and never found
+    fn fields(&self) -> &[StationSummary];
+
+    /// Add the measurements of a line, one per field, outside the main loop.
+    fn update_line(&mut self, measurements: &[i16], policy: impl RangePolicy);
+
+    /// Add the summaries of the same station from another worker.
+    fn merge(&mut self, other: &Self);
+
+    /// Parse a part of the file with the main loop for these summaries.
+    fn parse_part<'a, S, P>(
+        file: &'a [u8],
+        part: &'a [u8],
+        options: &ParseOptions<'a>,
+        policy: P,
+        measurements: &mut HashMap<'a, S, Self>,
+    ) where
+        S: BuildHasher + Default,
+        P: RangePolicy;
+
+    /// The lines of the station, including the dropped ones.
+    fn rows(&self, range: Option<Bounded>) -> u64 {
+        self.fields()
+            .first()
+            .map_or(0, |summary| summary.rows(range))
+    }
+}
+
+/// A single field, the 1BRC format, parsed by the batched main loop.
+impl Summaries for StationSummary {
+    fn fields(&self) -> &[StationSummary] {
+        std::slice::from_ref(self)
+    }
+
+    fn update_line(&mut self, measurements: &[i16], policy: impl RangePolicy) {
+        self.update(measurements[0], policy);
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+        self.outliers += other.outliers;
+    }
+
+    fn parse_part<'a, S, P>(
+        file: &'a [u8],
+        part: &'a [u8],
+        options: &ParseOptions<'a>,
+        policy: P,
+        measurements: &mut HashMap<'a, S, Self>,
+    ) where
+        S: BuildHasher + Default,
+        P: RangePolicy,
+    {
+        parse_part(file, part, options, policy, measurements);
+    }
+}
+
+/// Several fields, allocated on the first line of the station.
+#[derive(Default)]
+struct FieldSummaries(Vec<StationSummary>);
+impl Summaries for FieldSummaries {
+    fn fields(&self) -> &[StationSummary] {
+        &self.0
+    }
+
+    fn update_line(&mut self, measurements: &[i16], policy: impl RangePolicy) {
+        if self.0.is_empty() {
+            self.0
+                .resize_with(measurements.len(), StationSummary::default);
+        }
+        for (summary, &measurement) in self.0.iter_mut().zip(measurements) {
+            summary.update(measurement, policy);
+        }
+    }
+
+    fn merge(&mut self, other: &Self) {
+        if self.0.is_empty() {
+            self.0.resize_with(other.0.len(), StationSummary::default);
+        }
+        for (summary, other) in self.0.iter_mut().zip(&other.0) {
+            summary.merge(other);
+        }
+    }
+
+    fn parse_part<'a, S, P>(
+        file: &'a [u8],
+        part: &'a [u8],
+        options: &ParseOptions<'a>,
+        policy: P,
+        measurements: &mut HashMap<'a, S, Self>,
+    ) where
+        S: BuildHasher + Default,
+        P: RangePolicy,
+    {
+        parse_fields::<S, P, DefaultSearch>(file, part, options, policy, measurements);
+    }
+}
+
+struct StationSummary {
+    min: i16,
+    max: i16,
+    sum: i64,
+    count: u32,
+    /// Measurements out of the `--min` and `--max` range, not in `count` if they were dropped
+    outliers: u32,
+}
+impl Default for StationSummary {
+    fn default() -> Self {
+        Self {
+            min: i16::MAX,
+            max: i16::MIN,
+            sum: 0,
+            count: 0,
+            outliers: 0,
+        }
+    }
+}
+impl StationSummary {
+    #[inline(always)]
+    fn update(&mut self, measurement: i16, policy: impl RangePolicy) {
+        let Some(measurement) = policy.apply(measurement, &mut self.outliers) else {
+            return;
+        };
+        if hint::unlikely(measurement < self.min) {
+            self.min = measurement;
+        }
+        if hint::unlikely(measurement > self.max) {
+            self.max = measurement;
+        }
+        self.sum += measurement as i64;
+        self.count += 1;
+    }
+
+    /// The mean in tenths, see [`output::mean_tenths`].
+    fn mean_tenths(&self) -> i64 {
+        output::mean_tenths(self.sum, self.count as u64)
+    }
+
+    fn result<'a>(&self, name: Cow<'a, str>) -> StationResult<'a> {
+        StationResult {
+            name,
+            min: self.min as f64 / 10.0,
+            mean: self.mean_tenths() as f64 / 10.0,
+            max: self.max as f64 / 10.0,
+            count: self.count as u64,
+            sum_tenths: self.sum,
+            outliers: self.outliers as u64,
+        }
+    }
+
+    /// The lines of the station, including the dropped ones.
+    fn rows(&self, range: Option<Bounded>) -> u64 {
+        let dropped = if range.is_some_and(|range| range.drops()) {
+            self.outliers
+        } else {
+            0
+        };
+        (self.count + dropped) as u64
+    }
+}
+
+/// Parse a temperature followed by `end`, a newline or the separator of the next field, and move
+/// `file_ptr` past `end`.
+///
+/// # Safety
+///
+/// `file_ptr` must point to a temperature followed by `end` in `file`. The byte before it and the
+/// 8 bytes from its start must be within `file`, doesn't matter what they contain.
+#[inline(always)]
+unsafe fn parse_temperature<B: ByteSearch>(file: &[u8], file_ptr: &mut *const u8, end: u8) -> i16 {
+    let newline_pos = B::find_8(unsafe { read::<u64>(file, *file_ptr) }, end);
+    unsafe { hint::assert_unchecked(newline_pos < 8) };
+
+    /// # Safety
+    ///
+    /// `p..p + len` and the byte before `p` must be within `file`.
+    #[inline(always)]
+    unsafe fn parse_temperature_impl(file: &[u8], p: *const u8, len: isize) -> i16 {
+        let byte = |offset: isize| unsafe { read::<u8>(file, p.offset(offset)) };
+        let frac = byte(len - 1) - b'0';
+        let d0 = byte(len - 3) - b'0';
+        let d1 = byte(len - 4).wrapping_sub(b'0');
+        let positive = byte(0) != b'-';
+
+        let d1_valid = len >= 5 - (positive as isize);
+
+        let mut value =
+            /* digit -1 */  (frac as i16)
+            /* digit 0 */ + (d0 as i16 * 10)
+            /* digit 1 */ + ((d1 * (d1_valid as u8)) as i16 * 100);
+        value *= ((positive as i16) << 1) - 1;
+        value
+    }
+
+    let value = unsafe { parse_temperature_impl(file, *file_ptr, newline_pos as isize) };
+
+    #[cfg(debug_assertions)]
+    {
+        let s = unsafe { read::slice(file, *file_ptr, newline_pos) };
+        let s = str::from_utf8(s).unwrap();
+        let expected_value = s.parse::<f64>().unwrap();
+        debug_assert_eq!(
+            value,
+            (expected_value * 10.0) as i16,
+            "parsed value does not match standard library parsing for str '{s}'"
+        );
+    }
+
+    *file_ptr = unsafe { file_ptr.add(newline_pos + 1) }; // skip the newline or separator
+
+    value
+}
+
+/// Split `bytes` into `splits_num` parts of about the same size, each ending at a `record`
+/// separator, or at the end of `bytes`.
+#[inline(never)]
+fn split_bytes_aligned(bytes: &[u8], splits_num: usize, record: u8) -> Vec<&[u8]> {
+    assert!(splits_num >= 1);
+    let mut split_indices = Vec::with_capacity(splits_num - 1);
+    for i in 1..splits_num {
+        let idx = (i as f64 * bytes.len() as f64 / splits_num as f64) as usize;
+        let aligned_idx = bytes[idx..]
+            .iter()
+            .position(|&b| b == record)
+            .map_or(bytes.len(), |newline_pos| idx + newline_pos + 1);
+        split_indices.push(aligned_idx);
+    }
+    (0..splits_num)
+        .map(|i| {
+            let start = if i == 0 { 0 } else { split_indices[i - 1] };
+            let end = split_indices.get(i).copied().unwrap_or(bytes.len());
+            &bytes[start..end]
+        })
+        .collect()
+}
@@ -1,423 +1,266 @@
-#![feature(portable_simd)]
-#![feature(likely_unlikely)]
-
-mod hashmap;
-mod xor;
-
-use core::str;
-use std::hash::{Hash, Hasher};
-use std::simd::cmp::SimdPartialEq;
-use std::simd::{Simd, u8x16};
-
+mod args;
+mod bench;
+
+use std::collections::{BTreeMap, HashSet};
+
+use frenzy::output::{OutputOptions, StationResult};
+use frenzy::series::{self, SeriesResult};
+use frenzy::timings::{RunTimings, Stopwatch};
+use frenzy::{
+    Config, fast_field_results, fast_field_results_timed, fast_series_results, generate, group,
+    output, reference,
+};
 use memmap2::Mmap;
 
-use crate::hashmap::{KeyHashPair, SimpleHashMap};
-use crate::xor::XorHash;
-
-const DEBUG: bool = false;
-
-type HashMap<'a> = SimpleHashMap<StationName<'a>, StationSummary, XorHash>;
+use crate::args::{Args, Command};
 
 fn main() {
-    const { assert!(cfg!(target_endian = "little")) };
-
-    let measurements_file = std::env::args()
-        .nth(1)
-        .expect("Missing measurements file argument");
-    let file = std::fs::File::open(measurements_file).unwrap();
-    let mmap = unsafe { Mmap::map(&file).unwrap() };
-    let file_bytes: &[u8] = mmap.as_ref();
-
-    // sometimes we read 128 bytes ahead, without checking if we reached EOF.
-    // to avoid reading past EOF, we find the last newline before the last 128 bytes,
-    // and split the file there. The main loop will process the first part without bounds checks,
-    // and the second part (the "remainder") with bounds checks.
-    let remainder_idx = {
-        let idx = file_bytes.len() - 128;
-        idx - file_bytes[..idx]
-            .iter()
-            .rev()
-            .position(|&b| b == b'\n')
-            .unwrap()
-    };
-    let (file_bytes, mut file_bytes_remainder) = file_bytes.split_at(remainder_idx);
-
-    let workers_num = std::thread::available_parallelism().unwrap().get();
-    let mut measurements = std::thread::scope(|scope| {
-        // Split the file into chunks for each worker
-        let file_bytes = split_bytes_aligned(file_bytes, workers_num);
-
-        // Spawn worker threads
-        let workers = file_bytes
-            .into_iter()
-            .map(|file_bytes| scope.spawn(move || parse_file_bytes(file_bytes)))
-            .collect::<Vec<_>>();
-
-        // Merge results
-        let measurements = workers.into_iter().map(|w| w.join().unwrap()).reduce(
-            |mut measurements, worker_measurements| {
-                for (station_name, summary) in worker_measurements.iter() {
-                    let global_summary = measurements.get_or_default(*station_name);
-                    global_summary.min = global_summary.min.min(summary.min);
-                    global_summary.max = global_summary.max.max(summary.max);
-                    global_summary.sum += summary.sum;
-                    global_summary.count += summary.count;
-                }
-                measurements
-            },
-        );
-        measurements.unwrap()
-    });
-
-    // process remainder (trivially, no optimizations)
-    while !file_bytes_remainder.is_empty() {
-        let newline_pos = file_bytes_remainder
-            .iter()
-            .position(|&b| b == b'\n')
-            .unwrap();
-        let line = &file_bytes_remainder[..newline_pos];
-        file_bytes_remainder = &file_bytes_remainder[newline_pos + 1..]; // skip newline
-
-        let semicolon_pos = line.iter().position(|&b| b == b';').unwrap();
-        let name_bytes = &line[..semicolon_pos];
-        let measurement_bytes = &line[semicolon_pos + 1..]; // skip semicolon
-        let station_name = StationName::new(name_bytes);
-        let measurement = std::str::from_utf8(measurement_bytes)
-            .unwrap()
-            .parse::<f64>()
-            .unwrap();
-
-        measurements
-            .get_or_default(station_name)
-            .update((measurement * 10.0) as i16);
-    }
-
-    // output
-    // format: {Abha=-23.0/18.0/59.2, Abidjan=-16.2/26.0/67.3, Abéché=-10.0/29.4/69.0, Accra=-10.1/26.4/66.4, Addis Ababa=-23.7/16.0/67.0, Adelaide=-27.8/17.3/58.5, ...}
-    let mut measurements_sorted = measurements
-        .iter()
-        .map(|(name, m)| (name.to_str(), m))
-        .collect::<Vec<_>>();
-    measurements_sorted.sort_by(|(name_a, _), (name_b, _)| name_a.cmp(name_b));
-
-    let mut output = String::from("{");
-    for (station_name, summary) in &measurements_sorted {
-        output.push_str(&format!(
-            "{station_name}={:.1}/{:.1}/{:.1}, ",
-            (summary.min as f32).round() / 10.0,
-            (summary.sum as f64 / summary.count as f64).round() / 10.0,
-            (summary.max as f32).round() / 10.0,
-        ));
+    let args = Args::parse();
+    match args.command {
+        Command::Run if args.config.bucket.is_some() => run_series(&args),
+        Command::Run if args.reference => {
+            let file = std::fs::File::open(&args.measurements_file).unwrap();
+            let summaries =
+                reference::aggregate_fields(std::io::BufReader::new(file), &args.config);
+            let levels = summaries
+                .iter()
+                .flat_map(|summaries| {
+                    let mut results = reference::results(summaries);
+                    retain_listed(&mut results, args.config.stations.as_deref());
+                    output::arrange_levels(results, &args.output)
+                })
+                .collect::<Vec<_>>();
+            let (output, outliers) = format_levels(&levels, &args.config);
+            println!("{output}");
+            if let Some(outliers) = outliers {
+                eprintln!("{outliers}");
+            }
+        }
+        Command::Run => {
+            let run = run(&args.measurements_file, &args.config, &args.output);
+            println!("{}", run.output);
+            if let Some(outliers) = run.outliers {
+                eprintln!("{outliers}");
+            }
+            if args.timings {
+                eprintln!("{}", run.timings.report());
+            }
+        }
+        Command::DiffCheck => diff_check(&args),
+        Command::Bench(ref options) => bench::bench(&args.measurements_file, options),
+        Command::Generate(ref options) => {
+            let file = std::fs::File::create(&args.measurements_file).unwrap();
+            if let Err(err) = generate::generate(options, std::io::BufWriter::new(file)) {
+                eprintln!("error: {err}");
+                std::process::exit(1);
+            }
+        }
     }
-    output.pop();
-    output.pop();
-    output.push('}');
-    println!("{}", output);
+}
 
-    if DEBUG {
-        println!(
-            "Hashmap fallback size: {}/{}",
-            measurements.fallback_size(),
-            measurements_sorted.len()
-        );
-    }
+fn map_file(path: &std::path::Path) -> Mmap {
+    let file = std::fs::File::open(path).unwrap();
+    unsafe { Mmap::map(&file).unwrap() }
 }
 
-#[inline(never)]
-fn parse_file_bytes<'a>(file_bytes: &'a [u8]) -> HashMap<'a> {
-    // To utilize the CPU pipeline better, we maintain a batch of cursors into the file,
-    // and process them in parallel (in the same thread).
-    // Every variable that you expected to be u32, is now [u32; BATCH].
-    const BATCH: usize = 4;
-    fn batch<T>(f: impl FnMut(usize) -> T) -> [T; BATCH] {
-        std::array::from_fn(f)
-    }
+/// The result of [`run`].
+struct RunOutput {
+    output: String,
+    /// The report of the measurements out of `--min` and `--max`, if given
+    outliers: Option<String>,
+    timings: RunTimings,
+}
 
-    // Split the file into BATCH parts
-    let (mut file_ptr, file_end) = {
-        let splits = split_bytes_aligned(file_bytes, BATCH);
-        let file_ptr = batch(|bi| splits[bi].as_ptr());
-        let file_end = batch(|bi| unsafe { splits[bi].as_ptr().add(splits[bi].len()) });
-        (file_ptr, file_end)
+/// Run the whole pipeline with the fast engine, returning the output and the time of each phase.
+fn run(
+    measurements_file: &std::path::Path,
+    config: &Config,
+    output_options: &OutputOptions,
+) -> RunOutput {
+    let total = Stopwatch::start();
+    let stopwatch = Stopwatch::start();
+    let mmap = map_file(measurements_file);
+    let mmap_time = stopwatch.stop();
+
+    let (fields, engine) = fast_field_results_timed(&mmap, config);
+
+    let stopwatch = Stopwatch::start();
+    let levels = fields
+        .into_iter()
+        .flat_map(|results| output::arrange_levels(results, output_options))
+        .collect::<Vec<_>>();
+    let sort = stopwatch.stop();
+
+    let stopwatch = Stopwatch::start();
+    let (output, outliers) = format_levels(&levels, config);
+    let output_time = stopwatch.stop();
+
+    let mut total = total.stop();
+    // the main thread only waits for the workers
+    total.cpu = total
+        .cpu
+        .zip(engine.parse.cpu)
+        .map(|(main, workers)| main + workers);
+    let timings = RunTimings {
+        mmap: mmap_time,
+        engine,
+        sort,
+        output: output_time,
+        total,
     };
-
-    // Main loop
-    let mut measurements = HashMap::new(1000, 128.0);
-    while std::hint::likely((0..BATCH).all(|bi| file_ptr[bi] < file_end[bi])) {
-        // format: <string: station name>;<double: measurement>
-
-        // Read the name of the station
-        let first_word = batch(|bi| unsafe { file_ptr[bi].cast::<u128>().read_unaligned() });
-        let station_name = batch(|bi| unsafe {
-            StationName::parse_and_hash(&mut file_ptr[bi], first_word[bi], measurements.hasher())
-        });
-
-        // Read the temperature measurement
-        let measurement = batch(|bi| unsafe { parse_temperature(&mut file_ptr[bi]) });
-
-        // Update per-station summary
-        batch(|bi| {
-            measurements
-                .get_or_default(station_name[bi])
-                .update(measurement[bi]);
-        });
+    RunOutput {
+        output,
+        outliers,
+        timings,
     }
-
-    // Process remaining bytes in each batch cursor
-    batch(|bi| {
-        // same implementation as the main loop, but for a single cursor instead of BATCH
-
-        let (mut file_ptr, file_end) = (file_ptr[bi], file_end[bi]);
-        while std::hint::likely(file_ptr < file_end) {
-            let first_word = unsafe { file_ptr.cast::<u128>().read_unaligned() };
-            let station_name = unsafe {
-                StationName::parse_and_hash(&mut file_ptr, first_word, measurements.hasher())
-            };
-            let measurement = unsafe { parse_temperature(&mut file_ptr) };
-            measurements
-                .get_or_default(station_name)
-                .update(measurement);
-        }
-    });
-
-    measurements
 }
 
-#[derive(Clone, Copy)]
-struct StationName<'a> {
-    // The first 16 bytes of the name, stored as u128 for fast comparisons and hashing
-    // If the name is shorter than 16 bytes, the upper bytes are zeroed
-    prefix: u128,
-    // Pointer to the remainder of the name (after the first 16 bytes).
-    // Its valid to dereference the 16 bytes before this pointer, as they are part of the name.
-    // We store the pointer to the remainder instead of the beginning of the name as most of the times
-    // we want to access only the remainder (for equality checks and hashing).
-    remainder_ptr: *const u8,
-    // Length of the remainder (can be negative if the name is shorter than 16 bytes)
-    remainder_len: isize,
-    ph: std::marker::PhantomData<&'a [u8]>,
+/// The output of the levels of `--group-by` of each field, a line each, and their report of the
+/// measurements out of `--min` and `--max`, if given.
+fn format_levels(levels: &[Vec<StationResult>], config: &Config) -> (String, Option<String>) {
+    let output = levels
+        .iter()
+        .map(|level| output::format_sorted_results(level))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let outliers = config.range.map(|range| {
+        levels
+            .iter()
+            .map(|level| output::format_outliers(level, range.out_of_range))
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+    (output, outliers)
 }
-impl<'a> StationName<'a> {
-    pub fn new(name_bytes: &'a [u8]) -> Self {
-        let mut prefix_bytes = [0_u8; 16];
-        let prefix_len = name_bytes.len().min(16);
-        prefix_bytes[..prefix_len].copy_from_slice(&name_bytes[..prefix_len]);
-        let prefix = u128::from_ne_bytes(prefix_bytes);
-        Self::new_with_prefix(prefix, name_bytes)
-    }
 
-    pub fn new_with_prefix(prefix: u128, full_name: &'a [u8]) -> Self {
-        Self {
-            prefix,
-            remainder_ptr: unsafe { full_name.as_ptr().add(16) },
-            remainder_len: full_name.len().cast_signed() - 16,
-            ph: std::marker::PhantomData,
+/// `--bucket`: print the time series of each station, with the fast engine or the reference, and
+/// the report of the measurements out of `--min` and `--max` of each station, if given.
+fn run_series(args: &Args) {
+    let print = |mut results: Vec<SeriesResult>| {
+        let width = args.config.bucket.unwrap();
+        let filter = &args.output.filter;
+        results.retain(|series| filter.matches(&series.result.name));
+        println!("{}", series::format_series(&mut results, width));
+        if let Some(range) = args.config.range {
+            let mut stations =
+                group::merge_results(results.iter().map(|series| &series.result), |result| {
+                    Some(result.name.clone())
+                });
+            output::sort_results(&mut stations, Default::default());
+            eprintln!("{}", output::format_outliers(&stations, range.out_of_range));
         }
+    };
+    if args.reference {
+        let file = std::fs::File::open(&args.measurements_file).unwrap();
+        let summaries = reference::aggregate_series(std::io::BufReader::new(file), &args.config);
+        let mut results = reference::series_results(&summaries);
+        retain_listed_series(&mut results, args.config.stations.as_deref());
+        print(results);
+    } else {
+        let mmap = map_file(&args.measurements_file);
+        print(fast_series_results(&mmap, &args.config));
     }
+}
 
-    unsafe fn parse_and_hash(
-        file_ptr: &mut *const u8,
-        first_word: u128,
-        hash: &impl std::hash::BuildHasher,
-    ) -> KeyHashPair<Self> {
-        let mut name_prefix = first_word;
-        let name_length;
-        let mut hash = hash.build_hasher();
-
-        let semicolon_pos = u8x16::from_array(name_prefix.to_ne_bytes())
-            .simd_eq(u8x16::splat(b';'))
-            .to_bitmask()
-            .trailing_zeros() as usize;
-        if semicolon_pos <= 16 {
-            // fast path, semicolon is in the first 16 bytes
-
-            name_length = semicolon_pos;
-            // zero the upper bytes of name_prefix
-            name_prefix &= (1_u128.wrapping_shl((name_length * 8) as u32)) - 1;
-            hash.write_u128(name_prefix);
+/// Run both the fast engine and the reference implementation, and report any station whose
+/// output differs. Exits with an error code if there are differences.
+fn diff_check(args: &Args) {
+    if let Some(width) = args.config.bucket {
+        return diff_check_series(args, width);
+    }
+    let mmap = map_file(&args.measurements_file);
+    let fast = fast_field_results(&mmap, &args.config);
+
+    let file = std::fs::File::open(&args.measurements_file).unwrap();
+    let fields = args.config.fields.unwrap_or(1);
+    let summaries = reference::aggregate_fields(std::io::BufReader::new(file), &args.config);
+
+    let mut failed = false;
+    for (field, (fast, summaries)) in fast.iter().zip(&summaries).enumerate() {
+        let mut reference = reference::results(summaries);
+        retain_listed(&mut reference, args.config.stations.as_deref());
+        let differences = reference::diff(fast, &reference);
+        let field = if fields > 1 {
+            format!("field {}: ", field + 1)
         } else {
-            // slow path, semicolon is after the first 16 bytes
-
-            hash.write_u128(name_prefix);
-            let mut offset = 16;
-            loop {
-                const STEP_WORDS: usize = 1;
-                const STEP_BYTES: usize = STEP_WORDS * 8;
-                let words = unsafe {
-                    file_ptr
-                        .add(offset)
-                        .cast::<[u64; STEP_WORDS]>()
-                        .read_unaligned()
-                };
-                let words_bytes =
-                    unsafe { std::mem::transmute::<[u64; STEP_WORDS], [u8; STEP_BYTES]>(words) };
-                let semicolon_pos = Simd::<u8, _>::from_array(words_bytes)
-                    .simd_eq(Simd::splat(b';'))
-                    .to_bitmask()
-                    .trailing_zeros() as usize;
-                if semicolon_pos < STEP_BYTES {
-                    name_length = offset + semicolon_pos;
-                    for word in words.iter().take(semicolon_pos / 8) {
-                        hash.write_u64(*word);
-                    }
-                    hash.write_u64(
-                        words[semicolon_pos / 8]
-                            & ((1_u64.wrapping_shl(((semicolon_pos % 8) * 8) as u32)) - 1),
-                    );
-                    break;
-                }
-                offset += STEP_BYTES;
-                for word in words {
-                    hash.write_u64(word);
-                }
-            }
+            String::new()
         };
-
-        let full_name = unsafe { std::slice::from_raw_parts(*file_ptr, name_length) };
-        *file_ptr = unsafe { file_ptr.add(name_length + 1) }; // skip semicolon
-
-        let hash = hash.finish();
-        let name = StationName::new_with_prefix(name_prefix, full_name);
-        unsafe { KeyHashPair::new_unchecked(name, hash) }
-    }
-
-    fn remainder(&self) -> &[u8] {
-        debug_assert!(self.remainder_len >= 0);
-        unsafe {
-            std::slice::from_raw_parts(self.remainder_ptr, self.remainder_len.cast_unsigned())
+        for difference in &differences {
+            let missing = "<missing>".to_string();
+            println!(
+                "{field}{}:\n    fast:      {}\n    reference: {}",
+                difference.name,
+                difference.fast.as_ref().unwrap_or(&missing),
+                difference.reference.as_ref().unwrap_or(&missing),
+            );
         }
-    }
-
-    #[inline(never)]
-    #[cold]
-    fn to_str(&self) -> &str {
-        let len = (self.remainder_len + 16).cast_unsigned();
-        let full_name_ptr = unsafe { self.remainder_ptr.offset(-16) };
-        let full_name = unsafe { std::slice::from_raw_parts(full_name_ptr, len) };
-        str::from_utf8(full_name).unwrap()
-    }
-}
-impl Hash for StationName<'_> {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.prefix.hash(state);
-        if self.remainder_len > 0 {
-            state.write(self.remainder());
+        if differences.is_empty() {
+            println!("{field}OK: all {} stations match", reference.len());
+        } else {
+            println!(
+                "{field}{} of {} stations differ",
+                differences.len(),
+                reference.len().max(fast.len())
+            );
+            failed = true;
         }
     }
-}
-impl PartialEq for StationName<'_> {
-    fn eq(&self, other: &Self) -> bool {
-        if self.prefix != other.prefix {
-            return false;
-        }
-        if self.remainder_len <= 0 {
-            debug_assert_eq!(self.remainder_len, other.remainder_len);
-            return true; // prefixes are equal, and no remainders
-        }
-        if self.remainder_len != other.remainder_len {
-            return false;
-        }
-        self.remainder() == other.remainder()
+    if failed {
+        std::process::exit(1);
     }
 }
-impl Eq for StationName<'_> {}
-unsafe impl<'a> Send for StationName<'a> {}
-unsafe impl<'a> Sync for StationName<'a> {}
 
-struct StationSummary {
-    min: i16,
-    max: i16,
-    sum: i64,
-    count: u32,
-}
-impl Default for StationSummary {
-    fn default() -> Self {
-        Self {
-            min: i16::MAX,
-            max: i16::MIN,
-            sum: 0,
-            count: 0,
+/// [`diff_check`] of the time series of `--bucket`, station by station.
+fn diff_check_series(args: &Args, width: series::BucketWidth) {
+    let mmap = map_file(&args.measurements_file);
+    let mut fast = fast_series_results(&mmap, &args.config);
+    let file = std::fs::File::open(&args.measurements_file).unwrap();
+    let summaries = reference::aggregate_series(std::io::BufReader::new(file), &args.config);
+    let mut reference = reference::series_results(&summaries);
+    retain_listed_series(&mut reference, args.config.stations.as_deref());
+
+    let fast = series::station_lines(&mut fast, width)
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+    let reference = series::station_lines(&mut reference, width)
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+    let mut names = fast.keys().chain(reference.keys()).collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    let mut differences = 0;
+    for name in &names {
+        let (fast, reference) = (fast.get(*name), reference.get(*name));
+        if fast != reference {
+            let missing = "<missing>".to_string();
+            println!(
+                "{name}:\n    fast:      {}\n    reference: {}",
+                fast.unwrap_or(&missing),
+                reference.unwrap_or(&missing),
+            );
+            differences += 1;
         }
     }
-}
-impl StationSummary {
-    fn update(&mut self, measurement: i16) {
-        if std::hint::unlikely(measurement < self.min) {
-            self.min = measurement;
-        }
-        if std::hint::unlikely(measurement > self.max) {
-            self.max = measurement;
-        }
-        self.sum += measurement as i64;
-        self.count += 1;
+    if differences == 0 {
+        println!("OK: all {} stations match", reference.len());
+    } else {
+        println!("{differences} of {} stations differ", names.len());
+        std::process::exit(1);
     }
 }
 
-/// # Safety
-///
-/// It must be OK to dereference `s.as_ptr().offset(-1)``, doesn't matter what this address contains
-#[inline(always)]
-unsafe fn parse_temperature(file_ptr: &mut *const u8) -> i16 {
-    let newline_pos = Simd::from_array(unsafe { (*file_ptr).cast::<[u8; 8]>().read() })
-        .simd_eq(Simd::splat(b'\n'))
-        .to_bitmask()
-        .trailing_zeros() as usize;
-    unsafe { std::hint::assert_unchecked(newline_pos < 8) };
-    let s = unsafe { std::slice::from_raw_parts(*file_ptr, newline_pos) };
-
-    #[inline(always)]
-    unsafe fn parse_temperature_impl(s: &[u8]) -> i16 {
-        let len = s.len() as isize;
-        let p = s.as_ptr();
-        unsafe {
-            let frac = *p.offset(len - 1) - b'0';
-            let d0 = *p.offset(len - 3) - b'0';
-            let d1 = (*p.offset(len - 4)).wrapping_sub(b'0');
-            let positive = *p != b'-';
-
-            let d1_valid = len >= 5 - (positive as isize);
-
-            let mut value =
-                /* digit -1 */  (frac as i16)
-                /* digit 0 */ + (d0 as i16 * 10)
-                /* digit 1 */ + ((d1 * (d1_valid as u8)) as i16 * 100);
-            value *= ((positive as i16) << 1) - 1;
-            value
-        }
-    }
-
-    let value = unsafe { parse_temperature_impl(s) };
-
-    #[cfg(debug_assertions)]
-    {
-        let s = str::from_utf8(s).unwrap();
-        let expected_value = s.parse::<f64>().unwrap();
-        debug_assert_eq!(
-            value,
-            (expected_value * 10.0) as i16,
-            "parsed value does not match standard library parsing for str '{s}'"
-        );
+/// Keep only the stations of `--stations` in the time series, like [`retain_listed`].
+fn retain_listed_series(results: &mut Vec<SeriesResult>, stations: Option<&[String]>) {
+    if let Some(stations) = stations {
+        let stations = stations.iter().map(String::as_str).collect::<HashSet<_>>();
+        results.retain(|series| stations.contains(&*series.result.name));
     }
-
-    *file_ptr = unsafe { file_ptr.add(newline_pos + 1) }; // skip newline
-
-    value
 }
 
-#[inline(never)]
-fn split_bytes_aligned(bytes: &[u8], splits_num: usize) -> Vec<&[u8]> {
-    assert!(splits_num >= 1);
-    let mut split_indices = Vec::with_capacity(splits_num - 1);
-    for i in 1..splits_num {
-        let idx = (i as f64 * bytes.len() as f64 / splits_num as f64) as usize;
-        let aligned_idx = idx + bytes[idx..].iter().position(|&b| b == b'\n').unwrap() + 1;
-        split_indices.push(aligned_idx);
+/// Keep only the stations of `--stations`, which the fast engine skips while parsing.
+fn retain_listed(results: &mut Vec<StationResult>, stations: Option<&[String]>) {
+    if let Some(stations) = stations {
+        let stations = stations.iter().map(String::as_str).collect::<HashSet<_>>();
+        results.retain(|result| stations.contains(&*result.name));
     }
-    (0..splits_num)
-        .map(|i| {
-            let start = if i == 0 { 0 } else { split_indices[i - 1] };
-            let end = split_indices.get(i).copied().unwrap_or(bytes.len());
-            &bytes[start..end]
-        })
-        .collect()
 }
@@ -0,0 +1,580 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+
+use regex::Regex;
+
+use crate::collation::collation_key;
+use crate::group::{GroupKey, group_results};
+use crate::range::OutOfRange;
+
+/// The final result of a single station, in degrees.
+///
+/// Both the fast engine and the reference implementation produce these, and share the formatting
+/// below, so any difference in their output comes from the computed values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StationResult<'a> {
+    /// Borrowed from the input or the configuration, unless it had to be unescaped
+    pub name: Cow<'a, str>,
+    pub min: f64,
+    /// Already rounded to one decimal digit
+    pub mean: f64,
+    pub max: f64,
+    /// Number of measurements
+    pub count: u64,
+    /// Exact sum of the measurements, in tenths, to merge the results of several stations
+    pub sum_tenths: i64,
+    /// Measurements out of the `--min` and `--max` range, dropped or clamped
+    pub outliers: u64,
+}
+impl fmt::Display for StationResult<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}={}/{}/{}",
+            self.name,
+            OneDecimal(self.min),
+            OneDecimal(self.mean),
+            OneDecimal(self.max)
+        )
+    }
+}
+
+/// The mean in tenths of `count` measurements adding up to `sum_tenths`, rounded half toward
+/// positive infinity like the `Math.round` of the 1BRC baseline, computed exactly with integers.
+pub(crate) fn mean_tenths(sum_tenths: i64, count: u64) -> i64 {
+    let count = count as i64;
+    // floor(sum / count + 1/2)
+    (2 * sum_tenths + count).div_euclid(2 * count)
+}
+
+/// A value printed with exactly one decimal digit, from its integer number of tenths.
+///
+/// Zero is always printed as `0.0`: a value that rounds to zero, such as `-0.0` or `-0.04`, has no
+/// sign, as in the output of the 1BRC baseline.
+struct OneDecimal(f64);
+impl fmt::Display for OneDecimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tenths = (self.0 * 10.0).round() as i64;
+        let sign = if tenths < 0 { "-" } else { "" };
+        let abs = tenths.unsigned_abs();
+        write!(f, "{sign}{}.{}", abs / 10, abs % 10)
+    }
+}
+
+/// The value the stations are sorted by, selected with `--sort`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Mean,
+    Min,
+    Max,
+    Count,
+}
+impl SortKey {
+    pub const ALL: [SortKey; 5] = [
+        SortKey::Name,
+        SortKey::Mean,
+        SortKey::Min,
+        SortKey::Max,
+        SortKey::Count,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Mean => "mean",
+            SortKey::Min => "min",
+            SortKey::Max => "max",
+            SortKey::Count => "count",
+        }
+    }
+}
+impl std::str::FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|key| key.name() == s)
+            .ok_or_else(|| format!("unknown sort key '{s}'"))
+    }
+}
+
+/// How the station names are compared, selected with `--collation`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Collation {
+    /// By their UTF-8 bytes, the order of the 1BRC baseline
+    #[default]
+    Bytes,
+    /// Without case and accents first, so `Abéché` sorts next to `Abbottabad` and `İzmir` next to
+    /// `Istanbul`
+    Folded,
+}
+impl Collation {
+    pub const ALL: [Collation; 2] = [Collation::Bytes, Collation::Folded];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Collation::Bytes => "bytes",
+            Collation::Folded => "folded",
+        }
+    }
+
+    /// Compare two names. The folded keys are computed on each call, [`sort_results`] computes them
+    /// once per station.
+    fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            Collation::Bytes => a.cmp(b),
+            Collation::Folded => collation_key(a).cmp(&collation_key(b)).then(a.cmp(b)),
+        }
+    }
+}
+impl std::str::FromStr for Collation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|collation| collation.name() == s)
+            .ok_or_else(|| format!("unknown collation '{s}'"))
+    }
+}
+
+/// The order of the stations in the output, by station name by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SortOrder {
+    pub key: SortKey,
+    pub descending: bool,
+    /// Also used to order the stations with the same value of `key`
+    pub collation: Collation,
+}
+impl SortOrder {
+    /// Compare two results, `by_name` computing the order of their names with the collation.
+    ///
+    /// Stations with the same value are always in ascending order of their names, so the output
+    /// doesn't depend on the order of the hash map.
+    fn compare(
+        self,
+        a: &StationResult,
+        b: &StationResult,
+        by_name: impl FnOnce() -> Ordering,
+    ) -> Ordering {
+        let by_key = match self.key {
+            SortKey::Name => {
+                return if self.descending {
+                    by_name().reverse()
+                } else {
+                    by_name()
+                };
+            }
+            SortKey::Mean => a.mean.total_cmp(&b.mean),
+            SortKey::Min => a.min.total_cmp(&b.min),
+            SortKey::Max => a.max.total_cmp(&b.max),
+            SortKey::Count => a.count.cmp(&b.count),
+        };
+        let by_key = if self.descending {
+            by_key.reverse()
+        } else {
+            by_key
+        };
+        by_key.then_with(by_name)
+    }
+}
+
+/// The stations kept in the output with `--top` or `--bottom`, the first `count` ones in the order
+/// of `key`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Selection {
+    pub count: usize,
+    pub key: SortKey,
+    /// The highest values first, otherwise the lowest
+    pub top: bool,
+}
+impl Selection {
+    /// The order of the ranking, also the order of the output unless another one is given.
+    pub fn order(self, collation: Collation) -> SortOrder {
+        SortOrder {
+            key: self.key,
+            descending: self.top,
+            collation,
+        }
+    }
+}
+
+/// The stations kept in the output by their name, with `--prefix` and `--match`.
+#[derive(Clone, Debug, Default)]
+pub struct NameFilter {
+    pub prefix: Option<String>,
+    /// Matching any part of the name, unless anchored
+    pub pattern: Option<Regex>,
+}
+impl NameFilter {
+    pub fn matches(&self, name: &str) -> bool {
+        self.prefix
+            .as_ref()
+            .is_none_or(|prefix| name.starts_with(prefix.as_str()))
+            && self
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(name))
+    }
+}
+
+/// Which stations are in the output, and in which order.
+#[derive(Clone, Debug, Default)]
+pub struct OutputOptions {
+    pub filter: NameFilter,
+    /// All the stations if `None`
+    pub selection: Option<Selection>,
+    pub order: SortOrder,
+    /// The levels of groups in the output, one line each, only the stations if empty
+    pub group_by: Vec<GroupKey>,
+}
+
+/// Filter, select and sort the results for the output, in this order: `--top` selects among the
+/// stations matching the filter.
+pub fn arrange_results(results: &mut Vec<StationResult>, options: &OutputOptions) {
+    let filter = &options.filter;
+    if filter.prefix.is_some() || filter.pattern.is_some() {
+        results.retain(|result| filter.matches(&result.name));
+    }
+    if let Some(selection) = options.selection {
+        select_results(results, selection, options.order.collation);
+    }
+    sort_results(results, options.order);
+}
+
+/// The results merged at each level of `--group-by`, each level arranged like
+/// [`arrange_results`]. A single level of stations without groups.
+pub fn arrange_levels<'a>(
+    results: Vec<StationResult<'a>>,
+    options: &'a OutputOptions,
+) -> Vec<Vec<StationResult<'a>>> {
+    let mut levels = if options.group_by.is_empty() {
+        vec![results]
+    } else {
+        options
+            .group_by
+            .iter()
+            .map(|key| group_results(&results, key))
+            .collect()
+    };
+    for level in &mut levels {
+        arrange_results(level, options);
+    }
+    levels
+}
+
+/// Sort the results by station name and format them as
+/// `{Abha=-23.0/18.0/59.2, Abidjan=-16.2/26.0/67.3, Abéché=-10.0/29.4/69.0, ...}`
+pub fn format_results(results: &mut [StationResult]) -> String {
+    sort_results(results, SortOrder::default());
+    format_sorted_results(results)
+}
+
+/// Sort the results in the order of the output.
+pub fn sort_results(results: &mut [StationResult], order: SortOrder) {
+    match order.collation {
+        Collation::Bytes => results.sort_by(|a, b| order.compare(a, b, || a.name.cmp(&b.name))),
+        Collation::Folded => {
+            // the keys allocate, compute them once per station
+            let mut keyed = results
+                .iter()
+                .map(|result| (collation_key(&result.name), result.clone()))
+                .collect::<Vec<_>>();
+            keyed.sort_by(|(a_key, a), (b_key, b)| {
+                order.compare(a, b, || a_key.cmp(b_key).then(a.name.cmp(&b.name)))
+            });
+            for (result, (_, sorted)) in results.iter_mut().zip(keyed) {
+                *result = sorted;
+            }
+        }
+    }
+}
+
+/// Keep only the stations of `selection`, in no particular order.
+///
+/// The results are partitioned around the last selected one in linear time, rather than sorted,
+/// so only the selected ones are sorted for the output.
+pub fn select_results(
+    results: &mut Vec<StationResult>,
+    selection: Selection,
+    collation: Collation,
+) {
+    if selection.count >= results.len() {
+        return;
+    }
+    if let Some(last) = selection.count.checked_sub(1) {
+        let order = selection.order(collation);
+        results.select_nth_unstable_by(last, |a, b| {
+            // the names are only compared for equal values, rarely with the numeric keys
+            order.compare(a, b, || collation.compare(&a.name, &b.name))
+        });
+    }
+    results.truncate(selection.count);
+}
+
+/// Format results already sorted with [`sort_results`].
+pub fn format_sorted_results(results: &[StationResult]) -> String {
+    let output = results
+        .iter()
+        .map(|result| result.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{{{output}}}")
+}
+
+/// The measurements out of the `--min` and `--max` range of the stations of `results`, in their
+/// order, as `3 measurements out of range dropped, in 2 stations: Abha=2, Accra=1`.
+pub fn format_outliers(results: &[StationResult], out_of_range: OutOfRange) -> String {
+    let stations = results
+        .iter()
+        .filter(|result| result.outliers > 0)
+        .map(|result| format!("{}={}", result.name, result.outliers))
+        .collect::<Vec<_>>();
+    let total = results.iter().map(|result| result.outliers).sum::<u64>();
+    let action = match out_of_range {
+        OutOfRange::Drop => "dropped",
+        OutOfRange::Clamp => "clamped",
+    };
+    let mut report = format!("{total} measurements out of range {action}");
+    if !stations.is_empty() {
+        report.push_str(&format!(
+            ", in {} stations: {}",
+            stations.len(),
+            stations.join(", ")
+        ));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::Rng;
+
+    fn result(name: &str, mean: f64, count: u64) -> StationResult<'_> {
+        StationResult {
+            name: name.into(),
+            min: mean - 1.0,
+            mean,
+            max: mean + count as f64,
+            count,
+            sum_tenths: (mean * 10.0) as i64 * count as i64,
+            outliers: 0,
+        }
+    }
+
+    fn sorted_names(results: &[StationResult], order: SortOrder) -> Vec<String> {
+        let mut results = results.to_vec();
+        sort_results(&mut results, order);
+        results.iter().map(|r| r.name.to_string()).collect()
+    }
+
+    #[test]
+    fn sort_keys() {
+        let results = [
+            result("b", 2.0, 10),
+            result("a", 3.0, 30),
+            result("d", 1.0, 20),
+            result("c", 2.0, 5),
+        ];
+        let order = |key, descending| SortOrder {
+            key,
+            descending,
+            collation: Collation::Bytes,
+        };
+        assert_eq!(
+            sorted_names(&results, order(SortKey::Name, false)),
+            ["a", "b", "c", "d"]
+        );
+        assert_eq!(
+            sorted_names(&results, order(SortKey::Name, true)),
+            ["d", "c", "b", "a"]
+        );
+        // ties in ascending order of the names, in both directions
+        assert_eq!(
+            sorted_names(&results, order(SortKey::Mean, false)),
+            ["d", "b", "c", "a"]
+        );
+        assert_eq!(
+            sorted_names(&results, order(SortKey::Mean, true)),
+            ["a", "b", "c", "d"]
+        );
+        assert_eq!(
+            sorted_names(&results, order(SortKey::Min, false)),
+            ["d", "b", "c", "a"]
+        );
+        assert_eq!(
+            sorted_names(&results, order(SortKey::Max, true)),
+            ["a", "d", "b", "c"]
+        );
+        assert_eq!(
+            sorted_names(&results, order(SortKey::Count, false)),
+            ["c", "b", "d", "a"]
+        );
+        assert_eq!(
+            sorted_names(&results, order(SortKey::Count, true)),
+            ["a", "d", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn folded_collation() {
+        let names = [
+            "Abha",
+            "Abéché",
+            "Abbottabad",
+            "abha",
+            "Çanakkale",
+            "Denizli",
+            "İzmir",
+            "Istanbul",
+            "Iğdır",
+            "Ísafjörður",
+            "Zürich",
+            "Zagreb",
+            "Île-de-France",
+            "Ile-de-France",
+        ];
+        let results = names.map(|name| result(name, 0.0, 1));
+        let folded = SortOrder {
+            collation: Collation::Folded,
+            ..SortOrder::default()
+        };
+        assert_eq!(
+            sorted_names(&results, folded),
+            [
+                "Abbottabad",
+                "Abéché",
+                // same primary and secondary keys, by bytes
+                "Abha",
+                "abha",
+                "Çanakkale",
+                "Denizli",
+                "Iğdır",
+                // same primary key, `i` before `î` in the secondary one
+                "Ile-de-France",
+                "Île-de-France",
+                "Ísafjörður",
+                "Istanbul",
+                "İzmir",
+                "Zagreb",
+                "Zürich",
+            ]
+        );
+        let bytes = sorted_names(&results, SortOrder::default());
+        let mut expected = names.to_vec();
+        expected.sort();
+        assert_eq!(bytes, expected);
+
+        // the collation also orders the ties of the other keys
+        let descending = SortOrder {
+            key: SortKey::Mean,
+            descending: true,
+            ..folded
+        };
+        assert_eq!(
+            sorted_names(
+                &[result("Zürich", 1.0, 1), result("Éze", 1.0, 1)],
+                descending
+            ),
+            ["Éze", "Zürich"]
+        );
+    }
+
+    #[test]
+    fn selection_matches_full_sort() {
+        let mut rng = Rng::new(43);
+        let stations = if cfg!(miri) { 20 } else { 200 };
+        let names = (0..stations).map(|i| format!("s{i}")).collect::<Vec<_>>();
+        // few distinct values, for many ties
+        let results = names
+            .iter()
+            .map(|name| {
+                result(
+                    name,
+                    rng.range(0..=20) as f64 / 10.0,
+                    rng.range(1..=5) as u64,
+                )
+            })
+            .collect::<Vec<_>>();
+        for count in [0, 1, 7, stations - 1, stations, 500] {
+            for key in SortKey::ALL {
+                for top in [false, true] {
+                    for collation in Collation::ALL {
+                        let selection = Selection { count, key, top };
+                        let order = selection.order(collation);
+                        let mut selected = results.clone();
+                        select_results(&mut selected, selection, collation);
+                        sort_results(&mut selected, order);
+                        let mut expected = results.clone();
+                        sort_results(&mut expected, order);
+                        expected.truncate(count);
+                        assert_eq!(selected, expected);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn filter_before_selection() {
+        let results = [
+            result("Istanbul", 3.0, 1),
+            result("Izmir", 2.0, 1),
+            result("İzmir", 4.0, 1),
+            result("Ankara", 5.0, 1),
+            result("Irbid", 1.0, 1),
+        ];
+        let arranged = |options: &OutputOptions| {
+            let mut results = results.to_vec();
+            arrange_results(&mut results, options);
+            results
+                .iter()
+                .map(|r| r.name.to_string())
+                .collect::<Vec<_>>()
+        };
+        let prefix = NameFilter {
+            prefix: Some("I".to_string()),
+            pattern: None,
+        };
+        let pattern = NameFilter {
+            prefix: None,
+            pattern: Some(Regex::new("(?i)^i.*r").unwrap()),
+        };
+        let options = |filter: &NameFilter| OutputOptions {
+            filter: filter.clone(),
+            ..OutputOptions::default()
+        };
+        assert_eq!(arranged(&options(&prefix)), ["Irbid", "Istanbul", "Izmir"]);
+        assert_eq!(arranged(&options(&pattern)), ["Irbid", "Izmir"]);
+        let top = Selection {
+            count: 2,
+            key: SortKey::Mean,
+            top: true,
+        };
+        assert_eq!(
+            arranged(&OutputOptions {
+                filter: prefix,
+                selection: Some(top),
+                order: top.order(Collation::Bytes),
+                group_by: Vec::new(),
+            }),
+            ["Istanbul", "Izmir"]
+        );
+    }
+
+    #[test]
+    fn parse_options() {
+        for key in SortKey::ALL {
+            assert_eq!(key.name().parse(), Ok(key));
+        }
+        for collation in Collation::ALL {
+            assert_eq!(collation.name().parse(), Ok(collation));
+        }
+        assert!("average".parse::<SortKey>().is_err());
+    }
+}
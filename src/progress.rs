@@ -0,0 +1,95 @@
+//! The `--progress` line on stderr, for very large inputs.
+//!
+//! Each worker adds the size of every chunk of a few MB it parsed to its own atomic counter, and a
+//! reporter thread periodically sums them. Without `--progress` the workers parse their whole part
+//! at once, and there are no counters at all.
+
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// The workers update their counter after each chunk of this size.
+pub(crate) const CHUNK_SIZE: usize = 4 << 20;
+
+/// Bytes sampled at the start of the file to estimate the average line length, for the rows/s.
+const SAMPLE_SIZE: usize = 1 << 20;
+
+pub(crate) struct Progress {
+    /// Bytes parsed by each worker
+    workers: Vec<AtomicUsize>,
+    total_bytes: usize,
+    /// Average number of lines per byte of the file, estimated from its start
+    rows_per_byte: f64,
+    start: Instant,
+}
+impl Progress {
+    /// The progress of `file`, whose lines end with `record`.
+    pub fn new(file: &[u8], workers_num: usize, record: u8) -> Self {
+        let sample = &file[..file.len().min(SAMPLE_SIZE)];
+        let rows = sample.iter().filter(|&&b| b == record).count();
+        Self {
+            workers: (0..workers_num).map(|_| AtomicUsize::new(0)).collect(),
+            total_bytes: file.len(),
+            rows_per_byte: rows as f64 / sample.len().max(1) as f64,
+            start: Instant::now(),
+        }
+    }
+
+    /// The counter of the worker `i`.
+    pub fn worker(&self, i: usize) -> &AtomicUsize {
+        &self.workers[i]
+    }
+
+    /// Print the progress periodically until `done` is disconnected, at the end of the parsing.
+    ///
+    /// On a terminal the line is updated in place, otherwise a new line is printed every few seconds.
+    pub fn report(&self, done: Receiver<()>) {
+        let terminal = std::io::stderr().is_terminal();
+        let interval = Duration::from_millis(if terminal { 250 } else { 5000 });
+        loop {
+            let finished = match done.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => false,
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => true,
+            };
+            let line = self.line();
+            let mut stderr = std::io::stderr().lock();
+            if terminal {
+                // pad over a longer previous line
+                write!(stderr, "\r{line:<80}").unwrap();
+                if finished {
+                    writeln!(stderr).unwrap();
+                }
+            } else {
+                writeln!(stderr, "{line}").unwrap();
+            }
+            if finished {
+                return;
+            }
+        }
+    }
+
+    fn line(&self) -> String {
+        let done = self
+            .workers
+            .iter()
+            .map(|worker| worker.load(Ordering::Relaxed))
+            .sum::<usize>();
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let bytes_per_sec = done as f64 / elapsed;
+        let percent = 100.0 * done as f64 / self.total_bytes.max(1) as f64;
+        let eta = if done == 0 {
+            "-".to_string()
+        } else {
+            let eta = (self.total_bytes - done) as f64 / bytes_per_sec;
+            format!("{}:{:02}", eta as u64 / 60, eta as u64 % 60)
+        };
+        format!(
+            "{percent:5.1}% {:.1} / {:.1} GB, {:.1} GB/s, ~{:.1}M rows/s, ETA {eta}",
+            done as f64 / 1e9,
+            self.total_bytes as f64 / 1e9,
+            bytes_per_sec / 1e9,
+            bytes_per_sec * self.rows_per_byte / 1e6,
+        )
+    }
+}
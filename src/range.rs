@@ -0,0 +1,93 @@
+//! `--min` and `--max`, dropping or clamping the measurements out of a range.
+//!
+//! The check is a type parameter of the main loop, [`RangePolicy`], so without a range the loop is
+//! compiled with no checks at all.
+
+/// The valid range of the measurements, in degrees, both bounds included.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeasurementRange {
+    /// `-inf` for no lower bound
+    pub min: f64,
+    /// `inf` for no upper bound
+    pub max: f64,
+    pub out_of_range: OutOfRange,
+}
+impl MeasurementRange {
+    /// The bounds in tenths, as the measurements are compared in whole tenths.
+    pub(crate) fn bounds(&self) -> (i16, i16) {
+        // `as` saturates, the infinite bounds are the extreme values
+        (
+            (self.min * 10.0).round() as i16,
+            (self.max * 10.0).round() as i16,
+        )
+    }
+}
+
+/// What happens to the measurements out of the range. Either way they are counted, per station.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutOfRange {
+    /// Leave them out of the summary
+    #[default]
+    Drop,
+    /// Replace them by the closest bound
+    Clamp,
+}
+
+/// The measurement added to a summary, given the measurement read from the file.
+pub(crate) trait RangePolicy: Copy {
+    /// `None` to drop the measurement. Counts the measurements out of the range in `outliers`.
+    fn apply(self, measurement: i16, outliers: &mut u32) -> Option<i16>;
+}
+
+/// All the measurements as they are, the default.
+#[derive(Clone, Copy)]
+pub(crate) struct Unbounded;
+impl RangePolicy for Unbounded {
+    #[inline(always)]
+    fn apply(self, measurement: i16, _outliers: &mut u32) -> Option<i16> {
+        Some(measurement)
+    }
+}
+
+/// The measurements out of `min..=max`, in tenths, are dropped or clamped.
+#[derive(Clone, Copy)]
+pub(crate) struct Bounded {
+    min: i16,
+    max: i16,
+    clamp: bool,
+}
+impl Bounded {
+    pub fn new(range: &MeasurementRange) -> Self {
+        let (min, max) = range.bounds();
+        Self {
+            min,
+            max,
+            clamp: range.out_of_range == OutOfRange::Clamp,
+        }
+    }
+
+    /// Whether the measurements out of the range are left out of the summaries.
+    pub fn drops(self) -> bool {
+        !self.clamp
+    }
+}
+impl RangePolicy for Bounded {
+    #[inline(always)]
+    fn apply(self, measurement: i16, outliers: &mut u32) -> Option<i16> {
+        if crate::hint::likely(self.min <= measurement && measurement <= self.max) {
+            return Some(measurement);
+        }
+        *outliers += 1;
+        self.clamp.then(|| measurement.clamp(self.min, self.max))
+    }
+}
+/// For the lines parsed outside the main loop, which are not worth a copy per policy.
+impl RangePolicy for Option<Bounded> {
+    #[inline(always)]
+    fn apply(self, measurement: i16, outliers: &mut u32) -> Option<i16> {
+        match self {
+            None => Some(measurement),
+            Some(bounded) => bounded.apply(measurement, outliers),
+        }
+    }
+}
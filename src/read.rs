@@ -0,0 +1,45 @@
+//! Reads at the raw cursors of the parsing loop.
+//!
+//! The parsing loop reads whole words at raw pointers into the file, past the end of the current
+//! line and of the worker part, relying on the `READ_AHEAD` split to stay within the file. With the
+//! `checked` feature every read is checked against the bounds of the whole file instead, and goes
+//! through the file slice, so an out of bounds read panics and Miri sees the provenance of the file.
+
+/// Read a `T`, which must be valid for any bit pattern, at the possibly unaligned `ptr`.
+///
+/// # Safety
+///
+/// `ptr..ptr + size_of::<T>()` must be within `file`.
+#[inline(always)]
+pub(crate) unsafe fn read<T: Copy>(file: &[u8], ptr: *const u8) -> T {
+    #[cfg(feature = "checked")]
+    let ptr = unsafe { slice(file, ptr, size_of::<T>()) }.as_ptr();
+    #[cfg(not(feature = "checked"))]
+    let _ = file;
+    unsafe { ptr.cast::<T>().read_unaligned() }
+}
+
+/// The `len` bytes at `ptr`.
+///
+/// # Safety
+///
+/// `ptr..ptr + len` must be within `file`.
+#[inline(always)]
+pub(crate) unsafe fn slice(file: &[u8], ptr: *const u8, len: usize) -> &[u8] {
+    #[cfg(feature = "checked")]
+    {
+        let offset = ptr.addr().wrapping_sub(file.as_ptr().addr());
+        offset
+            .checked_add(len)
+            .and_then(|end| file.get(offset..end))
+            .unwrap_or_else(|| {
+                let offset = offset.cast_signed();
+                panic!("read of {len} bytes at offset {offset} is out of the file")
+            })
+    }
+    #[cfg(not(feature = "checked"))]
+    {
+        let _ = file;
+        unsafe { std::slice::from_raw_parts(ptr, len) }
+    }
+}
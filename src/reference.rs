@@ -0,0 +1,347 @@
+//! Straightforward implementation of the challenge, used as a reference to check the fast engine.
+
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use crate::output::StationResult;
+use crate::range::{MeasurementRange, OutOfRange};
+use crate::series::{self, SeriesResult};
+use crate::{Config, format};
+
+pub struct ReferenceSummary {
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: u64,
+    outliers: u64,
+}
+
+/// Compute the summaries of all the stations, line by line.
+pub fn aggregate(input: impl BufRead) -> HashMap<String, ReferenceSummary> {
+    aggregate_in_range(input, None)
+}
+
+/// Same as [`aggregate`], dropping or clamping the measurements out of `range`.
+pub fn aggregate_in_range(
+    input: impl BufRead,
+    range: Option<&MeasurementRange>,
+) -> HashMap<String, ReferenceSummary> {
+    let config = Config {
+        range: range.copied(),
+        ..Config::default()
+    };
+    aggregate_fields(input, &config).pop().unwrap()
+}
+
+/// Same as [`aggregate_in_range`], with the number of fields, the separators, the CSV format and the
+/// range of `config`. The summaries of each field, in their order in the lines.
+pub fn aggregate_fields(
+    input: impl BufRead,
+    config: &Config,
+) -> Vec<HashMap<String, ReferenceSummary>> {
+    let fields = config.fields.unwrap_or(1);
+    let delimiter = config.separators.delimiter as char;
+    let mut summaries = (0..fields)
+        .map(|_| HashMap::<String, ReferenceSummary>::new())
+        .collect::<Vec<_>>();
+    // in CSV, the first line that isn't a comment is a header unless it ends with a measurement, such
+    // as `12.3` but not `1e3` or `inf`, the same rule as the fast engine
+    let mut first_line = config.csv;
+    for line in input.split(config.separators.record) {
+        let line = String::from_utf8(line.unwrap()).unwrap();
+        if config.csv && line.starts_with('#') {
+            continue;
+        }
+        if std::mem::take(&mut first_line)
+            && !format::is_measurement(line.rsplit(delimiter).next().unwrap().as_bytes())
+        {
+            continue;
+        }
+        let (name, rest) = if config.csv && line.starts_with('"') {
+            unquote(&line, delimiter)
+        } else {
+            let (name, rest) = line.split_once(delimiter).unwrap();
+            (name.to_string(), rest)
+        };
+        let measurements = rest.split(delimiter).collect::<Vec<_>>();
+        assert_eq!(measurements.len(), fields, "{line}");
+        for (summaries, measurement) in summaries.iter_mut().zip(measurements) {
+            // `+ 0.0` turns `-0.0` into `0.0`, like the integer tenths of the fast engine
+            let measurement = measurement.parse::<f64>().unwrap() + 0.0;
+            let summary = summaries.entry(name.clone()).or_insert(ReferenceSummary {
+                min: f64::INFINITY,
+                max: f64::NEG_INFINITY,
+                sum: 0.0,
+                count: 0,
+                outliers: 0,
+            });
+            summary.add(measurement, config.range.as_ref());
+        }
+    }
+    summaries
+}
+
+/// The summaries of each station and time bucket of `config.bucket`, with the separators and the
+/// range of `config`, from lines of `<name>;<timestamp>;<temperature>`.
+pub fn aggregate_series(
+    input: impl BufRead,
+    config: &Config,
+) -> HashMap<(String, i64), ReferenceSummary> {
+    let width = config.bucket.expect("a bucket width");
+    let delimiter = config.separators.delimiter as char;
+    let mut summaries = HashMap::<(String, i64), ReferenceSummary>::new();
+    for line in input.split(config.separators.record) {
+        let line = String::from_utf8(line.unwrap()).unwrap();
+        let parts = line.split(delimiter).collect::<Vec<_>>();
+        let [name, timestamp, measurement] = parts[..] else {
+            panic!("expected <name>;<timestamp>;<temperature>: {line}");
+        };
+        let timestamp = series::parse_timestamp(timestamp.as_bytes()).unwrap();
+        // `+ 0.0` turns `-0.0` into `0.0`, like the integer tenths of the fast engine
+        let measurement = measurement.parse::<f64>().unwrap() + 0.0;
+        summaries
+            .entry((name.to_string(), width.bucket(timestamp)))
+            .or_insert(ReferenceSummary {
+                min: f64::INFINITY,
+                max: f64::NEG_INFINITY,
+                sum: 0.0,
+                count: 0,
+                outliers: 0,
+            })
+            .add(measurement, config.range.as_ref());
+    }
+    summaries
+}
+
+/// The quoted name at the start of `line` (RFC 4180) with its escaped quotes replaced, and the rest
+/// of the line after the delimiter that follows it.
+fn unquote(line: &str, delimiter: char) -> (String, &str) {
+    let mut name = String::new();
+    let mut chars = line.char_indices().skip(1);
+    while let Some((_, c)) = chars.next() {
+        if c != '"' {
+            name.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some((_, '"')) => name.push('"'),
+            Some((i, c)) if c == delimiter => return (name, &line[i + 1..]),
+            _ => panic!("expected the delimiter after a quoted name: {line}"),
+        }
+    }
+    panic!("unterminated quoted name: {line}")
+}
+
+/// `value` rounded to one decimal half toward positive infinity, like the
+/// `Math.round(value * 10.0) / 10.0` of the 1BRC baseline, from the floating point value itself
+/// rather than from integer tenths like the fast engine.
+///
+/// The floating point error of adding up the measurements is rounded away at a hundred millionth of
+/// a tenth first, so a mean of exactly `0.65` computed as `0.6499999999999999` still rounds up.
+fn round_half_up(value: f64) -> f64 {
+    let tenths = (value * 10.0 * 1e8).round() / 1e8;
+    (tenths + 0.5).floor() / 10.0
+}
+
+impl ReferenceSummary {
+    fn result<'a>(&self, name: &'a str) -> StationResult<'a> {
+        StationResult {
+            name: name.into(),
+            min: self.min,
+            mean: round_half_up(self.sum / self.count as f64),
+            max: self.max,
+            count: self.count,
+            sum_tenths: (self.sum * 10.0).round() as i64,
+            outliers: self.outliers,
+        }
+    }
+
+    fn add(&mut self, mut measurement: f64, range: Option<&MeasurementRange>) {
+        if let Some(range) = range {
+            let (min, max) = range.bounds();
+            let tenths = (measurement * 10.0).round();
+            if tenths < min.into() || tenths > max.into() {
+                self.outliers += 1;
+                match range.out_of_range {
+                    OutOfRange::Drop => return,
+                    OutOfRange::Clamp => {
+                        measurement = tenths.clamp(min.into(), max.into()) / 10.0;
+                    }
+                }
+            }
+        }
+        self.min = self.min.min(measurement);
+        self.max = self.max.max(measurement);
+        self.sum += measurement;
+        self.count += 1;
+    }
+}
+
+pub fn results(summaries: &HashMap<String, ReferenceSummary>) -> Vec<StationResult<'_>> {
+    summaries
+        .iter()
+        // all the measurements dropped
+        .filter(|(_, summary)| summary.count > 0)
+        .map(|(name, summary)| summary.result(name))
+        .collect()
+}
+
+/// The results of [`aggregate_series`].
+pub fn series_results(
+    summaries: &HashMap<(String, i64), ReferenceSummary>,
+) -> Vec<SeriesResult<'_>> {
+    summaries
+        .iter()
+        .filter(|(_, summary)| summary.count > 0)
+        .map(|((name, bucket), summary)| SeriesResult {
+            bucket: *bucket,
+            result: summary.result(name),
+        })
+        .collect()
+}
+
+/// A station whose result differs between the two engines, or that only one of them reported.
+pub struct Difference<'a> {
+    pub name: &'a str,
+    pub fast: Option<String>,
+    pub reference: Option<String>,
+}
+
+/// Compare the results of the fast engine and the reference, by their formatted output.
+pub fn diff<'a>(fast: &'a [StationResult], reference: &'a [StationResult]) -> Vec<Difference<'a>> {
+    let fast = fast
+        .iter()
+        .map(|r| (&*r.name, r.to_string()))
+        .collect::<HashMap<_, _>>();
+    let reference = reference
+        .iter()
+        .map(|r| (&*r.name, r.to_string()))
+        .collect::<HashMap<_, _>>();
+    let mut names = fast
+        .keys()
+        .chain(reference.keys())
+        .copied()
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let (fast, reference) = (fast.get(name), reference.get(name));
+            (fast != reference).then(|| Difference {
+                name,
+                fast: fast.cloned(),
+                reference: reference.cloned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CpuLevel;
+    use crate::hash::XorHash;
+    use crate::output::format_results;
+    use crate::test_utils::{Rng, measurements, random_name, rows};
+
+    fn fast_output(input: &str) -> String {
+        let (map, _) = crate::aggregate::<XorHash, crate::StationSummary>(
+            input.as_bytes(),
+            2,
+            crate::ParseOptions::new(CpuLevel::detect(), crate::DEFAULT_BATCH),
+            false,
+            &mut Default::default(),
+        );
+        let mut results = map
+            .iter()
+            .map(|(name, summary)| summary.result(name.to_str().into()))
+            .collect::<Vec<_>>();
+        format_results(&mut results)
+    }
+
+    fn reference_output(input: &str) -> String {
+        format_results(&mut results(&aggregate(input.as_bytes())))
+    }
+
+    #[test]
+    fn matches_fast_engine() {
+        let input = measurements(rows(20_000));
+        assert_eq!(reference_output(&input), fast_output(&input));
+
+        let mut rng = Rng::new(4);
+        let iters = if cfg!(miri) { 2 } else { 20 };
+        for _ in 0..iters {
+            let names = (0..rng.range(1..=30))
+                .map(|_| random_name(&mut rng, 100))
+                .collect::<Vec<_>>();
+            let mut input = String::new();
+            for _ in 0..rows(rng.range(0..=2000)) {
+                let value = rng.range(0..=1998) as f64 / 10.0 - 99.9;
+                input.push_str(&format!("{};{value:.1}\n", rng.choose(&names)));
+            }
+            assert_eq!(reference_output(&input), fast_output(&input));
+        }
+    }
+
+    #[test]
+    fn mean_rounds_half_up() {
+        let mean = |measurements: &[f64]| {
+            let mut summary = ReferenceSummary {
+                min: f64::INFINITY,
+                max: f64::NEG_INFINITY,
+                sum: 0.0,
+                count: 0,
+                outliers: 0,
+            };
+            for &measurement in measurements {
+                summary.add(measurement, None);
+            }
+            summary.result("a").mean
+        };
+        assert_eq!(mean(&[0.7, 0.6]), 0.7);
+        assert_eq!(mean(&[0.1, 0.2]), 0.2);
+        assert_eq!(mean(&[-0.7, -0.6]), -0.6);
+        assert_eq!(mean(&[-0.1, 0.0]), -0.0);
+        assert_eq!(mean(&[1.0, 1.0, 1.1]), 1.0);
+        assert_eq!(mean(&[99.9, 99.9, 99.8, 99.8]), 99.9);
+        assert_eq!(mean(&[-99.9, -99.9, -99.8, -99.8]), -99.8);
+    }
+
+    #[test]
+    fn diff_reports_differences() {
+        let result = |name: &'static str, min, mean, max| StationResult {
+            name: name.into(),
+            min,
+            mean,
+            max,
+            count: 1,
+            sum_tenths: (mean * 10.0) as i64,
+            outliers: 0,
+        };
+        let fast = [
+            result("a", 1.0, 2.0, 3.0),
+            result("b", 1.0, 2.0, 3.0),
+            result("c", 1.0, 2.0, 3.0),
+        ];
+        let reference = [
+            result("a", 1.0, 2.0, 3.0),
+            // equal after formatting
+            result("b", 1.0, 2.000001, 3.0),
+            result("c", 1.0, 2.1, 3.0),
+            result("d", 1.0, 2.0, 3.0),
+        ];
+        let differences = diff(&fast, &reference);
+        let differences = differences
+            .iter()
+            .map(|d| (d.name, d.fast.as_deref(), d.reference.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            differences,
+            [
+                ("c", Some("c=1.0/2.0/3.0"), Some("c=1.0/2.1/3.0")),
+                ("d", None, Some("d=1.0/2.0/3.0")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! Small deterministic PRNG (xorshift64*), for the measurements generator and randomized tests.
+
+pub(crate) struct Rng(u64);
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9e3779b97f4a7c15) | 1)
+    }
+
+    /// An independent generator for each `stream`, such as a chunk index, derived from `seed`.
+    pub fn with_stream(seed: u64, stream: u64) -> Self {
+        Self::new(splitmix64(splitmix64(seed) ^ stream))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    /// Uniform in `range`
+    pub fn range(&mut self, range: std::ops::RangeInclusive<usize>) -> usize {
+        range.start() + (self.next_u64() % (range.end() - range.start() + 1) as u64) as usize
+    }
+
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.range(0..=items.len() - 1)]
+    }
+
+    /// Uniform in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Standard normal distribution (Box-Muller)
+    pub fn gaussian(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64(); // in (0, 1], for the log
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+    }
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,368 @@
+//! `--bucket`, the time series of each station from lines of `<name>;<timestamp>;<temperature>`:
+//! the measurements are aggregated per station and time bucket, an hour, a day or a month.
+//!
+//! The timestamps are seconds since the Unix epoch, or ISO-8601 dates and times such as
+//! `2024-03-01`, `2024-03-01T13:05:00Z` or `2024-03-01 13:05:00.250+01:00`. The buckets are in UTC.
+
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use crate::output::StationResult;
+
+/// The width of the time buckets, selected with `--bucket`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BucketWidth {
+    Hour,
+    Day,
+    Month,
+}
+impl BucketWidth {
+    pub const ALL: [BucketWidth; 3] = [BucketWidth::Hour, BucketWidth::Day, BucketWidth::Month];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BucketWidth::Hour => "hour",
+            BucketWidth::Day => "day",
+            BucketWidth::Month => "month",
+        }
+    }
+
+    /// The start of the bucket of `timestamp`, both in seconds since the Unix epoch.
+    pub fn bucket(self, timestamp: i64) -> i64 {
+        match self {
+            BucketWidth::Hour => timestamp - timestamp.rem_euclid(3600),
+            BucketWidth::Day => timestamp - timestamp.rem_euclid(DAY),
+            BucketWidth::Month => {
+                let (year, month, _) = civil_from_days(timestamp.div_euclid(DAY));
+                days_from_civil(year, month, 1) * DAY
+            }
+        }
+    }
+
+    /// The label of the bucket starting at `bucket`: `2024-03-01T13`, `2024-03-01` or `2024-03`.
+    pub fn label(self, bucket: i64) -> String {
+        let (year, month, day) = civil_from_days(bucket.div_euclid(DAY));
+        match self {
+            BucketWidth::Hour => {
+                let hour = bucket.rem_euclid(DAY) / 3600;
+                format!("{year:04}-{month:02}-{day:02}T{hour:02}")
+            }
+            BucketWidth::Day => format!("{year:04}-{month:02}-{day:02}"),
+            BucketWidth::Month => format!("{year:04}-{month:02}"),
+        }
+    }
+}
+impl std::str::FromStr for BucketWidth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BucketWidth::ALL
+            .into_iter()
+            .find(|width| width.name() == s)
+            .ok_or_else(|| format!("unknown bucket width '{s}', expected hour, day or month"))
+    }
+}
+
+const DAY: i64 = 86400;
+
+/// The result of a station over a time bucket.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeriesResult<'a> {
+    /// The start of the bucket, in seconds since the Unix epoch
+    pub bucket: i64,
+    pub result: StationResult<'a>,
+}
+
+/// A timestamp in seconds since the Unix epoch: an integer number of seconds, possibly with a
+/// fraction, or an ISO-8601 date with an optional time and offset, UTC if it has none. The fractions
+/// of a second are truncated toward the past. `None` if it is neither.
+pub fn parse_timestamp(bytes: &[u8]) -> Option<i64> {
+    let s = std::str::from_utf8(bytes).ok()?;
+    if let Some(date) = s.get(..10).filter(|date| date.as_bytes()[4] == b'-') {
+        return parse_iso(date, &s[10..]);
+    }
+    let (seconds, fraction) = s.split_once('.').unwrap_or((s, "0"));
+    let digits = seconds.strip_prefix('-').unwrap_or(seconds);
+    if digits.is_empty() || !all_digits(digits) || fraction.is_empty() || !all_digits(fraction) {
+        return None;
+    }
+    let seconds = seconds.parse::<i64>().ok()?;
+    // -1.5 is in the second starting at -2
+    let past = seconds < 0 && fraction.bytes().any(|b| b != b'0');
+    Some(seconds - past as i64)
+}
+
+/// `YYYY-MM-DD`, and the rest of the timestamp: nothing, or `T` or a space followed by
+/// `HH:MM[:SS[.fraction]]` and an optional `Z` or `±HH[:MM]` offset.
+fn parse_iso(date: &str, rest: &str) -> Option<i64> {
+    let year = number(date, 0..4)?;
+    let month = number(date, 5..7)?;
+    let day = number(date, 8..10)?;
+    if date.as_bytes()[7] != b'-'
+        || !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+    {
+        return None;
+    }
+    let mut seconds = days_from_civil(year, month, day) * DAY;
+    if rest.is_empty() {
+        return Some(seconds);
+    }
+    let time = rest.strip_prefix(['T', ' '])?;
+    let (hour, minute) = (number(time, 0..2)?, number(time, 3..5)?);
+    if time.as_bytes()[2] != b':' || hour > 23 || minute > 59 {
+        return None;
+    }
+    seconds += hour * 3600 + minute * 60;
+    let mut rest = &time[5..];
+    if let Some(time) = rest.strip_prefix(':') {
+        let second = number(time, 0..2)?;
+        // a leap second
+        if second > 60 {
+            return None;
+        }
+        seconds += second;
+        rest = &time[2..];
+        if let Some(fraction) = rest.strip_prefix('.') {
+            let len = fraction.bytes().take_while(u8::is_ascii_digit).count();
+            if len == 0 {
+                return None;
+            }
+            rest = &fraction[len..];
+        }
+    }
+    let offset = match rest {
+        "" | "Z" => 0,
+        _ => {
+            let sign = match rest.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let offset = &rest[1..];
+            let (hours, minutes) = match offset.len() {
+                2 => (number(offset, 0..2)?, 0),
+                4 => (number(offset, 0..2)?, number(offset, 2..4)?),
+                5 if offset.as_bytes()[2] == b':' => (number(offset, 0..2)?, number(offset, 3..5)?),
+                _ => return None,
+            };
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            sign * (hours * 3600 + minutes * 60)
+        }
+    };
+    Some(seconds - offset)
+}
+
+/// The decimal number made of the digits of `s` in `range`.
+fn number(s: &str, range: std::ops::Range<usize>) -> Option<i64> {
+    let digits = s.get(range)?;
+    all_digits(digits).then(|| digits.parse().ok())?
+}
+
+fn all_digits(s: &str) -> bool {
+    s.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since the Unix epoch of a date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // years starting in March, so the leap day is the last day of the year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The year, month and day of a number of days since the Unix epoch, the inverse of
+/// [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// Sort the results by station name and bucket, and format them as one line per station, its
+/// buckets in chronological order:
+/// `Abha={2024-03-01=-2.0/15.3/30.1, 2024-03-02=1.2/17.0/33.4}`
+pub fn format_series(results: &mut [SeriesResult], width: BucketWidth) -> String {
+    station_lines(results, width)
+        .into_iter()
+        .map(|(_, line)| line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The lines of [`format_series`], with the name of their station.
+pub fn station_lines<'a>(
+    results: &mut [SeriesResult<'a>],
+    width: BucketWidth,
+) -> Vec<(Cow<'a, str>, String)> {
+    results.sort_by(|a, b| {
+        (a.result.name.as_ref(), a.bucket).cmp(&(b.result.name.as_ref(), b.bucket))
+    });
+    results
+        .chunk_by(|a, b| a.result.name == b.result.name)
+        .map(|station| {
+            let name = station[0].result.name.clone();
+            let mut line = format!("{name}={{");
+            for (i, series) in station.iter().enumerate() {
+                if i > 0 {
+                    line.push_str(", ");
+                }
+                let result = StationResult {
+                    name: Cow::Owned(width.label(series.bucket)),
+                    ..series.result.clone()
+                };
+                write!(line, "{result}").unwrap();
+            }
+            line.push('}');
+            (name, line)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp(b"0"), Some(0));
+        assert_eq!(parse_timestamp(b"1709298300"), Some(1709298300));
+        assert_eq!(parse_timestamp(b"1709298300.75"), Some(1709298300));
+        assert_eq!(parse_timestamp(b"-1.5"), Some(-2));
+        assert_eq!(parse_timestamp(b"-3600"), Some(-3600));
+        assert_eq!(parse_timestamp(b"2024-03-01"), Some(1709251200));
+        assert_eq!(parse_timestamp(b"2024-03-01T13:05"), Some(1709298300));
+        assert_eq!(parse_timestamp(b"2024-03-01T13:05:00Z"), Some(1709298300));
+        assert_eq!(
+            parse_timestamp(b"2024-03-01 13:05:00.250"),
+            Some(1709298300)
+        );
+        assert_eq!(
+            parse_timestamp(b"2024-03-01T14:05:00+01:00"),
+            Some(1709298300)
+        );
+        assert_eq!(
+            parse_timestamp(b"2024-03-01T08:35:00-0430"),
+            Some(1709298300)
+        );
+        assert_eq!(parse_timestamp(b"2024-03-01T15:05:00+02"), Some(1709298300));
+        assert_eq!(parse_timestamp(b"1969-12-31T23:59:59Z"), Some(-1));
+        assert_eq!(parse_timestamp(b"2024-02-29"), Some(1709164800));
+        assert_eq!(parse_timestamp(b"2000-02-29"), Some(951782400));
+
+        for invalid in [
+            "",
+            "-",
+            "12a",
+            "1.",
+            "2023-02-29",
+            "1900-02-29",
+            "2024-13-01",
+            "2024-03-00",
+            "2024/03/01",
+            "2024-03-01T",
+            "2024-03-01T24:00",
+            "2024-03-01T13:05:",
+            "2024-03-01T13:05:00.",
+            "2024-03-01T13:05:00+1",
+            "2024-03-01T13:05:00 Z",
+            "2024-03-01X13:05",
+        ] {
+            assert_eq!(parse_timestamp(invalid.as_bytes()), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn dates_round_trip() {
+        // every day of 1600..2400, covering all the leap year rules
+        let start = days_from_civil(1600, 1, 1);
+        let mut date = (1600, 1, 1);
+        for days in start..start + 292_194 {
+            assert_eq!(civil_from_days(days), date);
+            assert_eq!(days_from_civil(date.0, date.1, date.2), days);
+            date = if date.2 < days_in_month(date.0, date.1) {
+                (date.0, date.1, date.2 + 1)
+            } else if date.1 < 12 {
+                (date.0, date.1 + 1, 1)
+            } else {
+                (date.0 + 1, 1, 1)
+            };
+        }
+    }
+
+    #[test]
+    fn buckets() {
+        let t = parse_timestamp(b"2024-03-01T13:05:09Z").unwrap();
+        let label = |width: BucketWidth| width.label(width.bucket(t));
+        assert_eq!(label(BucketWidth::Hour), "2024-03-01T13");
+        assert_eq!(label(BucketWidth::Day), "2024-03-01");
+        assert_eq!(label(BucketWidth::Month), "2024-03");
+        assert_eq!(
+            BucketWidth::Month.bucket(t),
+            parse_timestamp(b"2024-03-01").unwrap()
+        );
+        let before_epoch = parse_timestamp(b"1969-12-31T23:30:00Z").unwrap();
+        assert_eq!(
+            BucketWidth::Hour.label(BucketWidth::Hour.bucket(before_epoch)),
+            "1969-12-31T23"
+        );
+        assert_eq!(
+            BucketWidth::Month.label(BucketWidth::Month.bucket(before_epoch)),
+            "1969-12"
+        );
+
+        for width in BucketWidth::ALL {
+            assert_eq!(width.name().parse(), Ok(width));
+        }
+        assert!("week".parse::<BucketWidth>().is_err());
+    }
+
+    #[test]
+    fn formatted_series() {
+        let result = |name: &'static str, day, mean| SeriesResult {
+            bucket: day * DAY,
+            result: StationResult {
+                name: name.into(),
+                min: mean - 1.0,
+                mean,
+                max: mean + 1.0,
+                count: 1,
+                sum_tenths: (mean * 10.0) as i64,
+                outliers: 0,
+            },
+        };
+        let mut results = [
+            result("Bern", 1, 2.0),
+            result("Abha", 31, 5.0),
+            result("Bern", 0, 1.0),
+            result("Abha", 0, 3.0),
+        ];
+        assert_eq!(
+            format_series(&mut results, BucketWidth::Day),
+            "Abha={1970-01-01=2.0/3.0/4.0, 1970-02-01=4.0/5.0/6.0}\n\
+             Bern={1970-01-01=0.0/1.0/2.0, 1970-01-02=1.0/2.0/3.0}"
+        );
+    }
+}
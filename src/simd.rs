@@ -0,0 +1,472 @@
+/// Byte search within a small block, the building block of the parsing kernels.
+///
+/// Implemented with `std::simd` when the `nightly` feature is enabled, and with SWAR (SIMD within
+/// a register) bit tricks on plain integers otherwise. Both implementations are always interchangeable.
+pub(crate) trait ByteSearch {
+    /// Index of the first byte in the (little endian) `block` equal to `byte`,
+    /// or a value `>= 16` if there is no such byte.
+    fn find_16(block: u128, byte: u8) -> usize;
+
+    /// Index of the first byte in the (little endian) `block` equal to `byte`,
+    /// or a value `>= 8` if there is no such byte.
+    fn find_8(block: u64, byte: u8) -> usize;
+
+    /// Index of the first byte in the (little endian) `words` equal to `byte`,
+    /// or a value `>= N * 8` if there is no such byte.
+    /// `N` must be 1, 2, 4 or 8, scanning 8 to 64 bytes at once.
+    fn find_words<const N: usize>(words: &[u64; N], byte: u8) -> usize;
+}
+
+#[cfg(feature = "nightly")]
+pub(crate) type DefaultSearch = PortableSimd;
+#[cfg(not(feature = "nightly"))]
+pub(crate) type DefaultSearch = Swar;
+
+#[cfg_attr(feature = "nightly", allow(dead_code))]
+pub(crate) struct Swar;
+impl ByteSearch for Swar {
+    #[inline(always)]
+    fn find_16(block: u128, byte: u8) -> usize {
+        const LO: u128 = u128::MAX / 0xff;
+        const HI: u128 = LO << 7;
+        // zero the bytes equal to `byte`, and then set the high bit of every zero byte.
+        // bytes above the first zero byte may be false positives due to the borrow, but we only
+        // care about the first one.
+        let x = block ^ (LO * byte as u128);
+        let found = x.wrapping_sub(LO) & !x & HI;
+        (found.trailing_zeros() / 8) as usize
+    }
+
+    #[inline(always)]
+    fn find_8(block: u64, byte: u8) -> usize {
+        (Self::found_mask(block, byte).trailing_zeros() / 8) as usize
+    }
+
+    #[inline(always)]
+    fn find_words<const N: usize>(words: &[u64; N], byte: u8) -> usize {
+        let masks = words.map(|word| Self::found_mask(word, byte));
+        if masks.iter().all(|&mask| mask == 0) {
+            return N * 8;
+        }
+        let word_idx = masks.iter().position(|&mask| mask != 0).unwrap();
+        word_idx * 8 + (masks[word_idx].trailing_zeros() / 8) as usize
+    }
+}
+#[cfg_attr(feature = "nightly", allow(dead_code))]
+impl Swar {
+    /// The high bit of the first byte equal to `byte` is set (and maybe some bytes after it).
+    #[inline(always)]
+    fn found_mask(block: u64, byte: u8) -> u64 {
+        const LO: u64 = u64::MAX / 0xff;
+        const HI: u64 = LO << 7;
+        let x = block ^ (LO * byte as u64);
+        x.wrapping_sub(LO) & !x & HI
+    }
+}
+
+#[cfg(feature = "nightly")]
+pub(crate) struct PortableSimd;
+#[cfg(feature = "nightly")]
+impl ByteSearch for PortableSimd {
+    #[inline(always)]
+    fn find_16(block: u128, byte: u8) -> usize {
+        use std::simd::cmp::SimdPartialEq;
+        std::simd::u8x16::from_array(block.to_ne_bytes())
+            .simd_eq(std::simd::u8x16::splat(byte))
+            .to_bitmask()
+            .trailing_zeros() as usize
+    }
+
+    #[inline(always)]
+    fn find_8(block: u64, byte: u8) -> usize {
+        Self::find_words(&[block], byte)
+    }
+
+    #[inline(always)]
+    fn find_words<const N: usize>(words: &[u64; N], byte: u8) -> usize {
+        use std::simd::Simd;
+        use std::simd::cmp::SimdPartialEq;
+        const { assert!(matches!(N, 1 | 2 | 4 | 8)) };
+
+        #[inline(always)]
+        fn bitmask<const LANES: usize>(bytes: &[u8], byte: u8) -> u64 {
+            Simd::<u8, LANES>::from_slice(bytes)
+                .simd_eq(Simd::splat(byte))
+                .to_bitmask()
+        }
+
+        let bytes = unsafe { std::slice::from_raw_parts(words.as_ptr().cast::<u8>(), N * 8) };
+        let mask = match N {
+            1 => bitmask::<8>(bytes, byte),
+            2 => bitmask::<16>(bytes, byte),
+            4 => bitmask::<32>(bytes, byte),
+            8 => bitmask::<64>(bytes, byte),
+            _ => unreachable!(),
+        };
+        mask.trailing_zeros() as usize
+    }
+}
+
+/// The byte search of the AVX2 variant of the main loop: a `vpcmpeqb` and a `vpmovmskb` per 16 or 32
+/// bytes.
+///
+/// Must only be used in the functions compiled with the AVX2 target features, which are only called
+/// on a CPU supporting them (see [`crate::cpu::CpuLevel::Avx2`]).
+#[cfg(target_arch = "x86_64")]
+pub(crate) struct Avx2;
+#[cfg(target_arch = "x86_64")]
+impl ByteSearch for Avx2 {
+    #[inline(always)]
+    fn find_16(block: u128, byte: u8) -> usize {
+        use std::arch::x86_64::*;
+        unsafe {
+            let block = _mm_loadu_si128(std::ptr::from_ref(&block).cast());
+            let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(block, _mm_set1_epi8(byte as i8)));
+            mask.trailing_zeros() as usize
+        }
+    }
+
+    #[inline(always)]
+    fn find_8(block: u64, byte: u8) -> usize {
+        use std::arch::x86_64::*;
+        let mask = unsafe {
+            let block = _mm_cvtsi64_si128(block as i64);
+            _mm_movemask_epi8(_mm_cmpeq_epi8(block, _mm_set1_epi8(byte as i8)))
+        };
+        // the upper 8 bytes are zeros, and may match a zero `byte`
+        (mask | 1 << 8).trailing_zeros() as usize
+    }
+
+    #[inline(always)]
+    fn find_words<const N: usize>(words: &[u64; N], byte: u8) -> usize {
+        use std::arch::x86_64::*;
+        const { assert!(matches!(N, 1 | 2 | 4 | 8)) };
+
+        let ptr = words.as_ptr().cast::<__m256i>();
+        let mask = |i: usize| unsafe {
+            let block = _mm256_loadu_si256(ptr.add(i));
+            _mm256_movemask_epi8(_mm256_cmpeq_epi8(block, _mm256_set1_epi8(byte as i8))) as u32
+        };
+        match N {
+            1 => Self::find_8(words[0], byte),
+            2 => Self::find_16(
+                unsafe { words.as_ptr().cast::<u128>().read_unaligned() },
+                byte,
+            ),
+            4 => mask(0).trailing_zeros() as usize,
+            8 => (mask(0) as u64 | (mask(1) as u64) << 32).trailing_zeros() as usize,
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// The byte search of the AVX-512 variant of the main loop: a `vpcmpeqb` into a mask register per
+/// 16, 32 or 64 bytes.
+///
+/// Must only be used in the functions compiled with the AVX-512 target features, which are only
+/// called on a CPU supporting them (see [`crate::cpu::CpuLevel::Avx512`]).
+#[cfg(target_arch = "x86_64")]
+pub(crate) struct Avx512;
+#[cfg(target_arch = "x86_64")]
+impl ByteSearch for Avx512 {
+    #[inline(always)]
+    fn find_16(block: u128, byte: u8) -> usize {
+        use std::arch::x86_64::*;
+        unsafe {
+            let block = _mm_loadu_si128(std::ptr::from_ref(&block).cast());
+            _mm_cmpeq_epi8_mask(block, _mm_set1_epi8(byte as i8)).trailing_zeros() as usize
+        }
+    }
+
+    #[inline(always)]
+    fn find_8(block: u64, byte: u8) -> usize {
+        Avx2::find_8(block, byte)
+    }
+
+    #[inline(always)]
+    fn find_words<const N: usize>(words: &[u64; N], byte: u8) -> usize {
+        use std::arch::x86_64::*;
+        const { assert!(matches!(N, 1 | 2 | 4 | 8)) };
+
+        match N {
+            1 => Self::find_8(words[0], byte),
+            2 => Self::find_16(
+                unsafe { words.as_ptr().cast::<u128>().read_unaligned() },
+                byte,
+            ),
+            4 => unsafe {
+                let block = _mm256_loadu_si256(words.as_ptr().cast());
+                _mm256_cmpeq_epi8_mask(block, _mm256_set1_epi8(byte as i8)).trailing_zeros()
+                    as usize
+            },
+            8 => unsafe {
+                let block = _mm512_loadu_si512(words.as_ptr().cast());
+                _mm512_cmpeq_epi8_mask(block, _mm512_set1_epi8(byte as i8)).trailing_zeros()
+                    as usize
+            },
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::BuildHasher;
+
+    use super::*;
+    use crate::StationName;
+    use crate::cpu::CpuLevel;
+    use crate::hash::{MulRotHash, XorHash};
+    use crate::hashmap::KeyHashPair;
+    use crate::test_utils::{
+        long_name_measurements, measurements, naive_summaries, parsed_part, rows, summaries,
+    };
+
+    /// Blocks with `byte` at every position, surrounded by bytes that are likely to trigger false
+    /// positives in the SWAR borrow logic, and blocks without it.
+    fn blocks<const N: usize>(byte: u8) -> Vec<[u8; N]> {
+        let fillers = [
+            0,
+            1,
+            byte - 1,
+            byte + 1,
+            byte ^ 0x80,
+            b'a',
+            0x7f,
+            0x80,
+            0xc3,
+            0xff,
+        ];
+        let mut blocks = Vec::new();
+        for &filler in &fillers {
+            blocks.push([filler; N]);
+            for pos in 0..N {
+                let mut block = [filler; N];
+                block[pos] = byte;
+                blocks.push(block);
+                for second in pos + 1..N {
+                    let mut block = block;
+                    block[second] = byte;
+                    blocks.push(block);
+                }
+            }
+        }
+        blocks
+    }
+
+    fn naive_find(block: &[u8], byte: u8) -> Option<usize> {
+        block.iter().position(|&b| b == byte)
+    }
+
+    fn check<Search: ByteSearch>() {
+        for byte in *b";\n," {
+            for block in blocks::<8>(byte) {
+                let pos = Search::find_8(u64::from_ne_bytes(block), byte);
+                match naive_find(&block, byte) {
+                    Some(expected) => assert_eq!(pos, expected, "{block:?}"),
+                    None => assert!(pos >= 8, "{block:?}"),
+                }
+            }
+            for block in blocks::<16>(byte) {
+                let pos = Search::find_16(u128::from_ne_bytes(block), byte);
+                match naive_find(&block, byte) {
+                    Some(expected) => assert_eq!(pos, expected, "{block:?}"),
+                    None => assert!(pos >= 16, "{block:?}"),
+                }
+            }
+            check_words::<Search, 1, 8>(byte);
+            check_words::<Search, 2, 16>(byte);
+            check_words::<Search, 4, 32>(byte);
+            check_words::<Search, 8, 64>(byte);
+        }
+    }
+
+    fn check_words<Search: ByteSearch, const N: usize, const BYTES: usize>(byte: u8) {
+        for block in blocks::<BYTES>(byte) {
+            let words: [u64; N] = std::array::from_fn(|i| {
+                u64::from_ne_bytes(block[i * 8..][..8].try_into().unwrap())
+            });
+            let pos = Search::find_words(&words, byte);
+            match naive_find(&block, byte) {
+                Some(expected) => assert_eq!(pos, expected, "{block:?}"),
+                None => assert!(pos >= BYTES, "{block:?}"),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "exhaustive, no unsafe code involved")]
+    fn swar_find() {
+        check::<Swar>();
+    }
+
+    #[cfg(feature = "nightly")]
+    #[test]
+    fn portable_simd_find() {
+        check::<PortableSimd>();
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn avx_find() {
+        if CpuLevel::Avx2.is_supported() {
+            check::<Avx2>();
+        }
+        if CpuLevel::Avx512.is_supported() {
+            check::<Avx512>();
+        }
+    }
+
+    /// The main loop with the byte search `B`.
+    fn parse<'a, B: ByteSearch>(input: &'a str, parsed: &'a [u8]) -> crate::HashMap<'a, XorHash> {
+        let mut map = crate::HashMap::new(1000, crate::HASHMAP_SAFETY_FACTOR);
+        let options =
+            crate::ParseOptions::new(crate::cpu::CpuLevel::Baseline, crate::DEFAULT_BATCH);
+        crate::parse_file_bytes_impl::<XorHash, _, B, { crate::DEFAULT_BATCH }, false, false>(
+            input.as_bytes(),
+            parsed,
+            &options,
+            crate::range::Unbounded,
+            &mut map,
+        );
+        map
+    }
+
+    #[test]
+    fn swar_pipeline_matches_naive() {
+        let input = measurements(rows(20_000));
+        let parsed = parsed_part(&input);
+        let map = parse::<Swar>(&input, parsed);
+        assert_eq!(summaries(&map), naive_summaries(parsed));
+    }
+
+    #[cfg(feature = "nightly")]
+    #[test]
+    fn portable_simd_pipeline_matches_swar() {
+        let input = measurements(rows(20_000));
+        let parsed = parsed_part(&input);
+        let swar = parse::<Swar>(&input, parsed);
+        let simd = parse::<PortableSimd>(&input, parsed);
+        assert_eq!(summaries(&simd), summaries(&swar));
+    }
+
+    #[test]
+    fn long_names_pipeline_matches_naive() {
+        let input = long_name_measurements(rows(20_000));
+        let parsed = parsed_part(&input);
+        let map = parse::<DefaultSearch>(&input, parsed);
+        assert_eq!(summaries(&map), naive_summaries(parsed));
+    }
+
+    /// Parse all lines in `bytes` with the given slow path step, calling `f` with each name.
+    /// `bytes` must be followed in `file` by enough bytes for the read ahead of the step.
+    fn for_each_name<'a, B: ByteSearch, const STEP_WORDS: usize>(
+        file: &'a [u8],
+        bytes: &'a [u8],
+        mut f: impl FnMut(KeyHashPair<StationName<'a>>),
+    ) {
+        let hasher = MulRotHash;
+        let (mut ptr, end) = (bytes.as_ptr(), bytes.as_ptr_range().end);
+        while ptr < end {
+            let first_word = unsafe { ptr.cast::<u128>().read_unaligned() };
+            let name = unsafe {
+                StationName::parse_and_hash::<B, STEP_WORDS>(
+                    file, &mut ptr, first_word, b';', &hasher,
+                )
+            };
+            unsafe { crate::parse_temperature::<B>(file, &mut ptr, b'\n') };
+            f(name);
+        }
+    }
+
+    fn parse_names<'a, B: ByteSearch, const STEP_WORDS: usize>(
+        file: &'a [u8],
+        bytes: &'a [u8],
+    ) -> Vec<(&'a [u8], u64)> {
+        let mut names = Vec::new();
+        for_each_name::<B, STEP_WORDS>(file, bytes, |name| {
+            names.push((name.key.to_str().as_bytes(), name.hash()))
+        });
+        names
+    }
+
+    /// The long names input, leaving enough bytes out for the read ahead of 64-byte steps.
+    fn long_names_input() -> String {
+        long_name_measurements(rows(5_000))
+    }
+    fn long_names_parsed(input: &str) -> &[u8] {
+        let end = input[..input.len() - 256].rfind('\n').unwrap() + 1;
+        &input.as_bytes()[..end]
+    }
+
+    #[test]
+    fn long_names_all_steps() {
+        let input = long_names_input();
+        let parsed = long_names_parsed(&input);
+        let expected = std::str::from_utf8(parsed)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let name = line.rsplit_once(';').unwrap().0.as_bytes();
+                (name, MulRotHash.hash_one(StationName::new(name)))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(parse_names::<Swar, 1>(input.as_bytes(), parsed), expected);
+        assert_eq!(parse_names::<Swar, 2>(input.as_bytes(), parsed), expected);
+        assert_eq!(parse_names::<Swar, 4>(input.as_bytes(), parsed), expected);
+        assert_eq!(parse_names::<Swar, 8>(input.as_bytes(), parsed), expected);
+        #[cfg(feature = "nightly")]
+        {
+            assert_eq!(
+                parse_names::<PortableSimd, 1>(input.as_bytes(), parsed),
+                expected
+            );
+            assert_eq!(
+                parse_names::<PortableSimd, 2>(input.as_bytes(), parsed),
+                expected
+            );
+            assert_eq!(
+                parse_names::<PortableSimd, 4>(input.as_bytes(), parsed),
+                expected
+            );
+            assert_eq!(
+                parse_names::<PortableSimd, 8>(input.as_bytes(), parsed),
+                expected
+            );
+        }
+    }
+
+    /// Throughput of the station name parsing with different slow path steps, on names of 20-100
+    /// bytes. Run with `cargo test --release -- --ignored --nocapture bench_long_names`.
+    #[test]
+    #[ignore]
+    fn bench_long_names() {
+        fn bench<const STEP_WORDS: usize>(input: &[u8], parsed: &[u8]) {
+            const ROUNDS: usize = 200;
+            let start = std::time::Instant::now();
+            for _ in 0..ROUNDS {
+                for_each_name::<DefaultSearch, STEP_WORDS>(
+                    input,
+                    std::hint::black_box(parsed),
+                    |name| {
+                        std::hint::black_box(name);
+                    },
+                );
+            }
+            let elapsed = start.elapsed().as_secs_f64();
+            let bytes_per_sec = (ROUNDS * parsed.len()) as f64 / elapsed;
+            println!(
+                "{:>2} bytes step: {:.2} GB/s",
+                STEP_WORDS * 8,
+                bytes_per_sec / 1e9
+            );
+        }
+
+        let input = long_names_input();
+        let parsed = long_names_parsed(&input);
+        bench::<1>(input.as_bytes(), parsed);
+        bench::<2>(input.as_bytes(), parsed);
+        bench::<4>(input.as_bytes(), parsed);
+        bench::<8>(input.as_bytes(), parsed);
+    }
+}
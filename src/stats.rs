@@ -0,0 +1,61 @@
+use std::fmt::Write;
+
+use crate::hashmap::HashMapStats;
+
+/// Print the hash map diagnostics requested with `--stats` to stderr: the map of each worker, their
+/// total, and the merged map with the fallback hits of the merge and the remainder.
+///
+/// The table is direct mapped (no probing), so every key in the table is found with a single probe,
+/// and every collision sends the key to the fallback map.
+pub(crate) fn print_hashmap_stats(
+    workers: &[HashMapStats],
+    merged: HashMapStats,
+    collisions: &[(&str, Option<&str>)],
+) {
+    let mut report = String::new();
+    writeln!(
+        report,
+        "{:<12} {:>10} {:>10} {:>10} {:>15}",
+        "hash map", "capacity", "occupied", "fallback", "fallback hits"
+    )
+    .unwrap();
+    let total = workers
+        .iter()
+        .fold(HashMapStats::default(), |total, stats| HashMapStats {
+            capacity: total.capacity + stats.capacity,
+            occupied: total.occupied + stats.occupied,
+            fallback_size: total.fallback_size + stats.fallback_size,
+            fallback_hits: total.fallback_hits + stats.fallback_hits,
+        });
+    let rows = workers
+        .iter()
+        .enumerate()
+        .map(|(i, stats)| (format!("worker {i}"), stats))
+        .chain([
+            ("all workers".to_string(), &total),
+            ("merged map".to_string(), &merged),
+        ]);
+    for (name, stats) in rows {
+        writeln!(
+            report,
+            "{name:<12} {:>10} {:>10} {:>10} {:>15}",
+            stats.capacity, stats.occupied, stats.fallback_size, stats.fallback_hits
+        )
+        .unwrap();
+    }
+
+    writeln!(
+        report,
+        "collisions in merged hash map: {}",
+        collisions.len()
+    )
+    .unwrap();
+    for (name, table_name) in collisions {
+        match table_name {
+            Some(table_name) => writeln!(report, "  {name:?} collides with {table_name:?}"),
+            None => writeln!(report, "  {name:?} hashes to the reserved bucket 0"),
+        }
+        .unwrap();
+    }
+    eprint!("{report}");
+}
@@ -0,0 +1,138 @@
+//! Branchless decoding of a batch of temperatures at once.
+//!
+//! Each cursor contributes the 8 bytes starting at its temperature, `-?\d?\d\.\d\n` followed by
+//! arbitrary bytes, as a little endian u64 word. The words are decoded all together:
+//! - The position of the decimal point is the only one of bytes 1..=3 with bit 4 (0x10) clear.
+//! - The sign is bit 4 of the first byte, clear for `-` and set for a digit.
+//! - The digits are shifted so the decimal point always lands in byte 3, masked out of their ASCII
+//!   representation, and combined into the value with a single multiplication by `100 << 24 | 10 << 16 | 1`,
+//!   which sums `100 * d1 + 10 * d0 + frac` into byte 4.
+//!
+//! With the `nightly` feature the words are held in a single `std::simd` register, otherwise the
+//! same operations are applied lane by lane on an array, which the compiler usually vectorises too.
+
+/// The decoding of `N` words at once, implemented for the batch widths `decode_batch` supports, so
+/// the main loop can be generic over its batch width.
+pub(crate) struct Batch<const N: usize>;
+pub(crate) trait DecodeBatch<const N: usize> {
+    /// Decode the temperatures of `N` words, returning the values (in tenths) and the length of
+    /// each temperature including its newline.
+    fn decode(words: [u64; N]) -> ([i16; N], [usize; N]);
+}
+#[cfg(not(feature = "nightly"))]
+impl<const N: usize> DecodeBatch<N> for Batch<N> {
+    #[inline(always)]
+    fn decode(words: [u64; N]) -> ([i16; N], [usize; N]) {
+        swar::decode_batch(words)
+    }
+}
+#[cfg(feature = "nightly")]
+impl<const N: usize> DecodeBatch<N> for Batch<N> {
+    #[inline(always)]
+    fn decode(words: [u64; N]) -> ([i16; N], [usize; N]) {
+        portable_simd::decode_batch(words)
+    }
+}
+
+const DOT_BITS: u64 = 0x10101000;
+const DIGITS_MASK: u64 = 0x0F000F0F00;
+const DIGITS_MUL: u64 = (100 << 24) | (10 << 16) | 1;
+
+mod swar {
+    use super::*;
+
+    /// Decode the temperatures of `N` words, returning the values (in tenths) and the length of
+    /// each temperature including its newline.
+    #[inline(always)]
+    #[cfg_attr(feature = "nightly", allow(dead_code))]
+    pub(crate) fn decode_batch<const N: usize>(words: [u64; N]) -> ([i16; N], [usize; N]) {
+        let dot_pos = words.map(|word| (!word & DOT_BITS).trailing_zeros() as u64);
+        let signed = words.map(|word| ((!word << 59) as i64) >> 63);
+        let values = std::array::from_fn(|i| {
+            let sign_mask = !((signed[i] as u64) & 0xff);
+            let digits = ((words[i] & sign_mask) << (28 - dot_pos[i])) & DIGITS_MASK;
+            let abs_value = ((digits.wrapping_mul(DIGITS_MUL)) >> 32) & 0x3ff;
+            ((abs_value as i64 ^ signed[i]) - signed[i]) as i16
+        });
+        let lens = dot_pos.map(|dot_pos| (dot_pos >> 3) as usize + 3);
+        (values, lens)
+    }
+}
+
+#[cfg(feature = "nightly")]
+mod portable_simd {
+    use std::simd::Simd;
+    use std::simd::num::{SimdInt, SimdUint};
+
+    use super::*;
+
+    /// Decode the temperatures of `N` words, returning the values (in tenths) and the length of
+    /// each temperature including its newline.
+    #[inline(always)]
+    pub(crate) fn decode_batch<const N: usize>(words: [u64; N]) -> ([i16; N], [usize; N]) {
+        let words = Simd::<u64, N>::from_array(words);
+        let dot_pos = (!words & Simd::splat(DOT_BITS)).trailing_zeros();
+        let signed = (!words << 59).cast::<i64>() >> 63;
+        let sign_mask = !(signed.cast::<u64>() & Simd::splat(0xff));
+        let digits =
+            ((words & sign_mask) << (Simd::splat(28) - dot_pos)) & Simd::splat(DIGITS_MASK);
+        let abs_value = ((digits * Simd::splat(DIGITS_MUL)) >> 32) & Simd::splat(0x3ff);
+        let values = (abs_value.cast::<i64>() ^ signed) - signed;
+        let lens = (dot_pos >> 3) + Simd::splat(3);
+        (
+            values.cast::<i16>().to_array(),
+            lens.cast::<usize>().to_array(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::all_temperatures;
+
+    /// The word read at the start of `temperature`, followed by a newline and `filler` bytes.
+    fn word(temperature: &str, filler: u8) -> u64 {
+        let mut bytes = [filler; 8];
+        bytes[..temperature.len()].copy_from_slice(temperature.as_bytes());
+        bytes[temperature.len()] = b'\n';
+        u64::from_le_bytes(bytes)
+    }
+
+    type Decode<const N: usize> = fn([u64; N]) -> ([i16; N], [usize; N]);
+
+    fn check<const N: usize>(decode: Decode<N>) {
+        let temperatures = all_temperatures();
+        for filler in [0, b'0', b'9', b'-', b'.', b'\n', b'A', 0xff] {
+            // rotate the values between the lanes, so every value is tested in every lane
+            for offset in 0..N {
+                for chunk in temperatures.chunks(N) {
+                    let lane = |i: usize| &chunk[(i + offset) % chunk.len()];
+                    let words = std::array::from_fn(|i| word(&lane(i).0, filler));
+                    let (values, lens) = decode(words);
+                    for i in 0..N {
+                        let (s, expected) = lane(i);
+                        assert_eq!(values[i], *expected, "'{s}' filler {filler}");
+                        assert_eq!(lens[i], s.len() + 1, "'{s}' filler {filler}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "exhaustive, no unsafe code involved")]
+    fn swar_all_values() {
+        check::<1>(swar::decode_batch);
+        check::<4>(swar::decode_batch);
+        check::<8>(swar::decode_batch);
+    }
+
+    #[cfg(feature = "nightly")]
+    #[test]
+    fn portable_simd_all_values() {
+        check::<1>(portable_simd::decode_batch);
+        check::<4>(portable_simd::decode_batch);
+        check::<8>(portable_simd::decode_batch);
+    }
+}
@@ -0,0 +1,281 @@
+//! Helpers shared by the tests of different modules.
+
+use std::collections::BTreeMap;
+
+use crate::cpu::CpuLevel;
+use crate::format::Separators;
+use crate::hash::HashKind;
+use crate::output::StationResult;
+pub(crate) use crate::rng::Rng;
+use crate::{BATCH_WIDTHS, Config, HashMap, reference};
+
+/// The station names used by the official 1BRC generator.
+pub(crate) fn station_names() -> Vec<Vec<u8>> {
+    include_str!("../data/weather_stations.csv")
+        .lines()
+        .map(|line| line.rsplit_once(';').unwrap().0.as_bytes().to_vec())
+        .collect()
+}
+
+/// `value` tenths as a 1BRC temperature, without going through float formatting, which is slow
+/// under Miri.
+pub(crate) fn tenths(value: i32) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    format!("{sign}{}.{}", value.abs() / 10, value.abs() % 10)
+}
+
+/// Every valid 1BRC temperature with its value in tenths, including `-0.0`.
+pub(crate) fn all_temperatures() -> Vec<(String, i16)> {
+    let mut temperatures = (-999..=999)
+        .map(|v: i16| (tenths(v.into()), v))
+        .collect::<Vec<_>>();
+    temperatures.push(("-0.0".to_string(), 0));
+    temperatures
+}
+
+/// Deterministic measurements file content, cycling over the 1BRC station names.
+pub(crate) fn measurements(rows: usize) -> String {
+    let mut input = String::new();
+    for (i, name) in station_names().iter().cycle().take(rows).enumerate() {
+        let name = std::str::from_utf8(name).unwrap();
+        let value = (i as i32).wrapping_mul(7919).rem_euclid(1999) - 999;
+        input.push_str(&format!("{name};{}\n", tenths(value)));
+    }
+    input
+}
+
+/// Deterministic measurements file content with names of 20 to 100 bytes.
+pub(crate) fn long_name_measurements(rows: usize) -> String {
+    let stations = station_names()
+        .into_iter()
+        .map(|name| String::from_utf8(name).unwrap())
+        .collect::<Vec<_>>();
+    let names = (0..500)
+        .map(|i| {
+            let target_len = 20 + (i * 37) % 81;
+            let mut name = format!("{i:03}");
+            for station in stations.iter().cycle().skip(i) {
+                if name.len() + 1 + station.len() > target_len {
+                    break;
+                }
+                name.push('/');
+                name.push_str(station);
+            }
+            // pad to the exact target length
+            while name.len() < target_len {
+                name.push('_');
+            }
+            name
+        })
+        .collect::<Vec<_>>();
+
+    let mut input = String::new();
+    for (i, name) in names.iter().cycle().take(rows).enumerate() {
+        let value = (i as i32).wrapping_mul(7919).rem_euclid(1999) - 999;
+        input.push_str(&format!("{name};{}\n", tenths(value)));
+    }
+    input
+}
+
+/// `rows`, or few enough rows for a test to run under Miri in reasonable time.
+pub(crate) fn rows(rows: usize) -> usize {
+    if cfg!(miri) { rows.min(100) } else { rows }
+}
+
+/// The part of `input` the main loop may parse, leaving out the last `READ_AHEAD` bytes as `run`
+/// does, as the loop reads ahead of the cursor.
+pub(crate) fn parsed_part(input: &str) -> &[u8] {
+    let end = input[..input.len() - crate::READ_AHEAD]
+        .rfind('\n')
+        .unwrap()
+        + 1;
+    &input.as_bytes()[..end]
+}
+
+/// (min, max, sum, count) per station, sorted by name
+pub(crate) type Summaries<'a> = Vec<(&'a str, i16, i16, i64, u32)>;
+
+pub(crate) fn summaries<'a, S>(map: &HashMap<'a, S>) -> Summaries<'a> {
+    let mut summaries = map
+        .iter()
+        .map(|(name, s)| (name.to_str(), s.min, s.max, s.sum, s.count))
+        .collect::<Vec<_>>();
+    summaries.sort();
+    summaries
+}
+
+/// Straightforward line by line computation of the summaries, to test against.
+pub(crate) fn naive_summaries(bytes: &[u8]) -> Summaries<'_> {
+    let mut map = BTreeMap::<&str, (i16, i16, i64, u32)>::new();
+    for line in std::str::from_utf8(bytes).unwrap().lines() {
+        let (name, value) = line.rsplit_once(';').unwrap();
+        let value = (value.parse::<f64>().unwrap() * 10.0).round() as i16;
+        let s = map.entry(name).or_insert((i16::MAX, i16::MIN, 0, 0));
+        *s = (s.0.min(value), s.1.max(value), s.2 + value as i64, s.3 + 1);
+    }
+    map.into_iter()
+        .map(|(name, (min, max, sum, count))| (name, min, max, sum, count))
+        .collect()
+}
+
+/// Random station name of at most `max_len` bytes (and at least 1), mixing ASCII and multi-byte
+/// UTF-8 characters.
+pub(crate) fn random_name(rng: &mut Rng, max_len: usize) -> String {
+    const CHARS: &[char] = &['a', 'Z', ' ', '-', '.', '\'', 'é', 'ß', 'İ', '€', '東', '𝄞'];
+    let target_len = rng.range(1..=max_len);
+    let mut name = String::new();
+    loop {
+        let c = *rng.choose(CHARS);
+        if name.len() + c.len_utf8() > target_len {
+            break;
+        }
+        name.push(c);
+    }
+    if name.is_empty() {
+        name.push('a');
+    }
+    name
+}
+
+/// The lines of [`random_measurements`].
+#[derive(Clone, Copy)]
+pub(crate) struct Layout {
+    /// Number of measurements after the name
+    pub fields: usize,
+    pub separators: Separators,
+    /// A CSV file: maybe comment lines and a header, and names containing the delimiter or quotes,
+    /// quoted with their quotes escaped, or for some of the names with quotes left unquoted
+    pub csv: bool,
+    /// A timestamp between the name and the measurements, in seconds since the epoch or ISO-8601,
+    /// from 2024-02-27 to 2024-03-01 so the buckets cross days and months
+    pub timestamps: bool,
+}
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            fields: 1,
+            separators: Separators::default(),
+            csv: false,
+            timestamps: false,
+        }
+    }
+}
+
+/// Random lines of `layout` over a few random station names, with measurements of every valid
+/// value. Returns the names, and the input.
+pub(crate) fn random_measurements(rng: &mut Rng, layout: Layout) -> (Vec<String>, String) {
+    let Layout {
+        fields,
+        separators,
+        csv,
+        timestamps,
+    } = layout;
+    let (delimiter, record) = (separators.delimiter as char, separators.record as char);
+    let names = (0..rng.range(1..=30))
+        .map(|_| {
+            if !csv {
+                return random_name(rng, 100);
+            }
+            let mut name = random_name(rng, 90);
+            for _ in 0..rng.range(0..=2) {
+                let i = name.char_indices().map(|(i, _)| i).collect::<Vec<_>>();
+                name.insert(*rng.choose(&i), *rng.choose(&[delimiter, '"']));
+            }
+            name
+        })
+        .collect::<Vec<_>>();
+
+    let mut input = String::new();
+    if csv && rng.range(0..=1) == 0 {
+        input.push_str(&format!("# exported{record}"));
+    }
+    if csv && rng.range(0..=1) == 0 {
+        input.push_str(&format!("\"station\"{delimiter}\"temperature\"{record}"));
+    }
+    for _ in 0..rows(rng.range(0..=3000)) {
+        if csv && rng.range(0..=20) == 0 {
+            input.push_str(&format!("# comment{delimiter} with \"quotes\"{record}"));
+        }
+        let name = rng.choose(&names);
+        // the names with quotes are quoted half of the time, and must be if they contain the
+        // delimiter or start with a quote
+        let quoted = csv
+            && (name.contains(delimiter)
+                || name.starts_with('"')
+                || (name.contains('"') && rng.range(0..=1) == 0)
+                || rng.range(0..=3) == 0);
+        if quoted {
+            input.push_str(&format!("\"{}\"", name.replace('"', "\"\"")));
+        } else {
+            input.push_str(name);
+        }
+        if timestamps {
+            input.push(delimiter);
+            input.push_str(&random_timestamp(rng));
+        }
+        for _ in 0..fields {
+            let value = rng.range(0..=1998) as f64 / 10.0 - 99.9;
+            input.push_str(&format!("{delimiter}{value:.1}"));
+        }
+        input.push(record);
+    }
+    (names, input)
+}
+
+/// A timestamp from 2024-02-27 to 2024-03-01, in whole or fractional seconds since the epoch, or
+/// as an ISO-8601 time in +02:00 or date.
+fn random_timestamp(rng: &mut Rng) -> String {
+    const DATES: [&str; 4] = ["2024-02-27", "2024-02-28", "2024-02-29", "2024-03-01"];
+    let seconds = rng.range(0..=4 * 86400 - 7201) as i64;
+    match rng.range(0..=3) {
+        0 => (1_708_992_000 + seconds).to_string(),
+        1 => format!("{}.{}", 1_708_992_000 + seconds, rng.range(0..=999)),
+        2 => {
+            let (day, second) = ((seconds + 7200) / 86400, (seconds + 7200) % 86400);
+            let (h, m, s) = (second / 3600, second / 60 % 60, second % 60);
+            format!("{}T{h:02}:{m:02}:{s:02}+02:00", DATES[day as usize])
+        }
+        _ => rng.choose(&DATES).to_string(),
+    }
+}
+
+/// A random number of workers, batch width, supported CPU level and hash function.
+pub(crate) fn random_engine(rng: &mut Rng) -> Config {
+    let cpus = CpuLevel::ALL
+        .into_iter()
+        .filter(|cpu| cpu.is_supported())
+        .collect::<Vec<_>>();
+    Config {
+        workers: Some(rng.range(1..=4)),
+        batch: Some(*rng.choose(&BATCH_WIDTHS)),
+        cpu: Some(*rng.choose(&cpus)),
+        hash: *rng.choose(&HashKind::ALL),
+        ..Config::default()
+    }
+}
+
+/// The name, outliers and count of each result, sorted.
+pub(crate) fn outliers(results: &[StationResult]) -> Vec<(String, u64, u64)> {
+    let mut outliers = results
+        .iter()
+        .map(|result| (result.name.to_string(), result.outliers, result.count))
+        .collect::<Vec<_>>();
+    outliers.sort();
+    outliers
+}
+
+/// Assert that the results of the fast engine are the reference ones, including their outliers.
+/// With a station list in `config`, the reference results of the other stations are left out.
+pub(crate) fn assert_same_results(
+    fast: &[StationResult],
+    mut reference: Vec<StationResult>,
+    config: &Config,
+    input: &str,
+) {
+    if let Some(stations) = &config.stations {
+        reference.retain(|result| stations.iter().any(|name| *name == result.name));
+    }
+    let differences = reference::diff(fast, &reference);
+    assert!(differences.is_empty(), "{config:?}\n{input}");
+    assert_eq!(outliers(fast), outliers(&reference), "{config:?}\n{input}");
+}
@@ -0,0 +1,508 @@
+use std::hash::BuildHasher;
+
+use crate::cpu::CpuLevel;
+use crate::format::Separators;
+use crate::hash::{MulRotHash, XorHash};
+use crate::simd::{ByteSearch, DefaultSearch, Swar};
+use crate::test_utils::{
+    Layout, Rng, all_temperatures, assert_same_results, measurements, naive_summaries, outliers,
+    parsed_part, random_engine, random_measurements, random_name, rows, summaries,
+};
+use crate::*;
+
+fn check_parse_temperature<B: ByteSearch>() {
+    // a sample of the values under Miri
+    let step = if cfg!(miri) { 97 } else { 1 };
+    for (s, expected) in all_temperatures().into_iter().step_by(step) {
+        for filler in [0, b'0', b'-', b'\n', 0xff] {
+            // one byte before the temperature may be read, and 8 bytes from its start
+            let mut buf = vec![filler];
+            buf.extend_from_slice(s.as_bytes());
+            buf.push(b'\n');
+            buf.extend_from_slice(&[filler; 8]);
+
+            let start = unsafe { buf.as_ptr().add(1) };
+            let mut ptr = start;
+            let value = unsafe { parse_temperature::<B>(&buf, &mut ptr, b'\n') };
+            assert_eq!(value, expected, "'{s}'");
+            assert_eq!(ptr, unsafe { start.add(s.len() + 1) }, "'{s}'");
+        }
+    }
+}
+
+#[test]
+fn parse_temperature_all_values() {
+    check_parse_temperature::<Swar>();
+    check_parse_temperature::<DefaultSearch>();
+}
+
+fn check_parse_and_hash<B: ByteSearch, const STEP_WORDS: usize>(
+    name: &str,
+    hasher: &impl BuildHasher,
+) {
+    let mut buf = name.as_bytes().to_vec();
+    buf.extend_from_slice(b";-12.3\n");
+    let line_len = buf.len();
+    buf.extend_from_slice(&[b'x'; READ_AHEAD]);
+
+    let mut ptr = buf.as_ptr();
+    let first_word = unsafe { ptr.cast::<u128>().read_unaligned() };
+    let pair = unsafe {
+        StationName::parse_and_hash::<B, STEP_WORDS>(&buf, &mut ptr, first_word, b';', hasher)
+    };
+    let expected = StationName::new(name.as_bytes());
+    assert!(pair.key == expected, "'{name}'");
+    assert_eq!(pair.hash(), hasher.hash_one(expected), "'{name}'");
+    assert_eq!(pair.key.to_str(), name);
+    assert_eq!(ptr, unsafe { buf.as_ptr().add(name.len() + 1) }, "'{name}'");
+
+    assert_eq!(
+        unsafe { parse_temperature::<B>(&buf, &mut ptr, b'\n') },
+        -123
+    );
+    assert_eq!(ptr, unsafe { buf.as_ptr().add(line_len) });
+}
+
+#[test]
+fn parse_and_hash_matches_new() {
+    let mut rng = Rng::new(1);
+    let mut names = Vec::new();
+    // no random names under Miri
+    let random_names = if cfg!(miri) { 0 } else { 10 };
+    for len in 1..=100 {
+        names.push("a".repeat(len));
+        names.push("é".repeat(len / 2) + &"a".repeat(len % 2));
+        for _ in 0..random_names {
+            names.push(random_name(&mut rng, len));
+        }
+    }
+    for name in &names {
+        check_parse_and_hash::<Swar, NAME_STEP_WORDS>(name, &XorHash);
+        check_parse_and_hash::<Swar, NAME_STEP_WORDS>(name, &MulRotHash);
+        check_parse_and_hash::<DefaultSearch, NAME_STEP_WORDS>(name, &MulRotHash);
+        check_parse_and_hash::<DefaultSearch, 1>(name, &MulRotHash);
+    }
+}
+
+#[test]
+fn station_name_eq() {
+    let names = [
+        "a",
+        "b",
+        "ab",
+        "a\0",
+        "0123456789abcdef",
+        "0123456789abcdefg",
+    ]
+    .into_iter()
+    .map(String::from)
+    .chain((16..40).map(|len| "x".repeat(len)))
+    .collect::<Vec<_>>();
+    for a in &names {
+        for b in &names {
+            let (na, nb) = (
+                StationName::new(a.as_bytes()),
+                StationName::new(b.as_bytes()),
+            );
+            // names are never equal to a name with a trailing zero byte that fits in the prefix,
+            // as the prefix zero padding makes them indistinguishable, but such names are not valid
+            if a.trim_end_matches('\0') == b.trim_end_matches('\0') && a.len().max(b.len()) <= 16 {
+                continue;
+            }
+            assert_eq!(na == nb, a == b, "'{a}' '{b}'");
+        }
+    }
+}
+
+#[test]
+fn split_bytes_aligned_invariants() {
+    let mut rng = Rng::new(2);
+    let iters = if cfg!(miri) { 10 } else { 200 };
+    for _ in 0..iters {
+        let lines = rng.range(0..=50);
+        let mut input = Vec::new();
+        for _ in 0..lines {
+            input.extend_from_slice(random_name(&mut rng, 30).as_bytes());
+            input.extend_from_slice(b";1.0\n");
+        }
+        for splits_num in 1..=16 {
+            let splits = split_bytes_aligned(&input, splits_num, b'\n');
+            assert_eq!(splits.len(), splits_num);
+            assert_eq!(splits.concat(), input);
+            let mut start = 0;
+            for split in &splits {
+                // every split starts at the beginning of a line, and ends at the end of one
+                assert!(start == 0 || input[start - 1] == b'\n');
+                assert!(split.is_empty() || split.last() == Some(&b'\n'));
+                start += split.len();
+            }
+        }
+    }
+}
+
+/// The full pipeline (split to workers, main loop, merge and remainder) against a naive
+/// implementation, on random inputs.
+#[test]
+fn aggregate_matches_naive() {
+    let mut rng = Rng::new(3);
+    // under Miri (with the `checked` feature), a few small inputs still cover the whole pipeline
+    let iters = if cfg!(miri) { 6 } else { 60 };
+    for iter in 0..iters {
+        let names = (0..rng.range(1..=60))
+            .map(|_| random_name(&mut rng, 100))
+            .collect::<Vec<_>>();
+        // small inputs test the files smaller than the read ahead
+        let rows = if iter % 3 == 0 {
+            rng.range(0..=10)
+        } else if cfg!(miri) {
+            rng.range(0..=100)
+        } else {
+            rng.range(0..=3000)
+        };
+        let mut input = String::new();
+        for _ in 0..rows {
+            let value = rng.range(0..=1998) as f64 / 10.0 - 99.9;
+            input.push_str(&format!("{};{value:.1}\n", rng.choose(&names)));
+        }
+        let expected = naive_summaries(input.as_bytes());
+
+        let workers_num = rng.range(1..=4);
+        let batch = *rng.choose(&BATCH_WIDTHS);
+        let cpu = *rng.choose(
+            &CpuLevel::ALL
+                .into_iter()
+                .filter(|cpu| cpu.is_supported())
+                .collect::<Vec<_>>(),
+        );
+        if iter % 2 == 0 {
+            let (map, _) = aggregate::<XorHash, StationSummary>(
+                input.as_bytes(),
+                workers_num,
+                ParseOptions::new(cpu, batch),
+                false,
+                &mut Default::default(),
+            );
+            assert_eq!(summaries(&map), expected, "{input}");
+        } else {
+            let (map, _) = aggregate::<MulRotHash, StationSummary>(
+                input.as_bytes(),
+                workers_num,
+                ParseOptions::new(cpu, batch),
+                false,
+                &mut Default::default(),
+            );
+            assert_eq!(summaries(&map), expected, "{input}");
+        }
+    }
+}
+
+/// The options of a case of [`features_match_reference`] besides the layout of its input, given the
+/// station names of the input.
+type Options = fn(&mut Rng, &[String], &mut Config);
+
+/// Some of the stations of the input, and one missing from it.
+fn listed_stations(rng: &mut Rng, names: &[String], config: &mut Config) {
+    let mut listed = (0..rng.range(0..=10))
+        .map(|_| rng.choose(names).clone())
+        .collect::<Vec<_>>();
+    listed.push("missing from the file".to_string());
+    config.stations = Some(listed);
+}
+
+/// A random range dropping or clamping the measurements out of it, maybe without one of its bounds.
+fn random_range(rng: &mut Rng, _: &[String], config: &mut Config) {
+    use crate::range::{MeasurementRange, OutOfRange};
+
+    let bound = |rng: &mut Rng| rng.range(0..=1998) as f64 / 10.0 - 99.9;
+    let (a, b) = (bound(rng), bound(rng));
+    let (min, max) = match rng.range(0..=3) {
+        0 => (f64::NEG_INFINITY, a.max(b)),
+        1 => (a.min(b), f64::INFINITY),
+        _ => (a.min(b), a.max(b)),
+    };
+    let out_of_range = *rng.choose(&[OutOfRange::Drop, OutOfRange::Clamp]);
+    config.range = Some(MeasurementRange {
+        min,
+        max,
+        out_of_range,
+    });
+}
+
+/// Every option of the input and of the aggregation that the reference supports, on random inputs
+/// with random engine configurations: each field of the results, with their outliers, is the same as
+/// the reference.
+#[test]
+fn features_match_reference() {
+    let csv = Layout {
+        separators: Separators::new(b',', b'\n').unwrap(),
+        csv: true,
+        ..Layout::default()
+    };
+    let separators = |delimiter, record, fields| Layout {
+        fields,
+        separators: Separators::new(delimiter, record).unwrap(),
+        ..Layout::default()
+    };
+    let fields = |fields| Layout {
+        fields,
+        ..Layout::default()
+    };
+    let cases: [(Layout, Options); 11] = [
+        (Layout::default(), |_, _, _| {}),
+        (Layout::default(), listed_stations),
+        (Layout::default(), random_range),
+        (fields(3), |_, _, _| {}),
+        (fields(MAX_FIELDS), random_range),
+        (separators(b',', b'\n', 1), |_, _, _| {}),
+        (separators(b'\t', b'\n', 2), |_, _, _| {}),
+        (separators(b'|', b'\r', 1), random_range),
+        (separators(b';', b'|', 3), |_, _, _| {}),
+        (csv, |_, _, _| {}),
+        (csv, listed_stations),
+    ];
+
+    let mut rng = Rng::new(44);
+    let iters = if cfg!(miri) { 2 } else { 20 };
+    for (layout, options) in cases {
+        for _ in 0..iters {
+            let (names, input) = random_measurements(&mut rng, layout);
+            let mut config = Config {
+                fields: Some(layout.fields),
+                separators: layout.separators,
+                csv: layout.csv,
+                ..random_engine(&mut rng)
+            };
+            options(&mut rng, &names, &mut config);
+            let fast = fast_field_results(input.as_bytes(), &config);
+            let summaries = reference::aggregate_fields(input.as_bytes(), &config);
+            assert_eq!(fast.len(), layout.fields);
+            for (fast, summaries) in fast.iter().zip(&summaries) {
+                assert_same_results(fast, reference::results(summaries), &config, &input);
+            }
+        }
+    }
+}
+
+/// With a station list, the map is seeded with the listed stations, and those missing from the file
+/// keep an empty summary.
+#[test]
+fn listed_stations_are_seeded() {
+    let input = measurements(rows(2000));
+    let listed = ["Abha", "Birao", "missing from the file"].map(str::to_string);
+    let cpu = CpuLevel::detect();
+    for batch in BATCH_WIDTHS {
+        let (map, _) = aggregate::<MulRotHash, StationSummary>(
+            input.as_bytes(),
+            2,
+            ParseOptions {
+                stations: Some(&listed),
+                ..ParseOptions::new(cpu, batch)
+            },
+            false,
+            &mut Default::default(),
+        );
+        let mut expected = naive_summaries(input.as_bytes())
+            .into_iter()
+            .filter(|(name, ..)| listed.iter().any(|l| l == name))
+            .collect::<Vec<_>>();
+        expected.push(("missing from the file", i16::MAX, i16::MIN, 0, 0));
+        expected.sort();
+        assert_eq!(summaries(&map), expected);
+    }
+}
+
+/// Only the quoted names are unescaped, `a""b` staying `a""b` unquoted, `"a""b"` and the unquoted
+/// `a"b` are the same station, and the header lines ending with a number that isn't a measurement are
+/// skipped by both engines.
+#[test]
+fn csv_quotes_and_header() {
+    let config = Config {
+        separators: Separators::new(b',', b'\n').unwrap(),
+        csv: true,
+        ..Config::default()
+    };
+    for header in ["", "x,1e3\n", "x,inf\n"] {
+        // repeated for the main loop to parse most of it
+        let lines = "\"a\"\"b\",1.0\na\"\"b,2.0\n\"a\"\"b\",3.0\na\"b,4.0\n";
+        let input = header.to_string() + &lines.repeat(100);
+        let expected = r#"{a""b=2.0/2.0/2.0, a"b=1.0/2.7/4.0}"#;
+        let mut fast = fast_field_results(input.as_bytes(), &config).pop().unwrap();
+        assert_eq!(output::format_results(&mut fast), expected, "{header}");
+        let summaries = reference::aggregate_fields(input.as_bytes(), &config);
+        let mut reference = reference::results(&summaries[0]);
+        assert_eq!(output::format_results(&mut reference), expected, "{header}");
+    }
+}
+
+/// Time series of every bucket width, from epoch and ISO-8601 timestamps, also with a list of
+/// stations or a range.
+#[test]
+fn series_match_reference() {
+    use crate::series::{BucketWidth, format_series};
+
+    let mut rng = Rng::new(50);
+    let iters = if cfg!(miri) { 4 } else { 40 };
+    for iter in 0..iters {
+        let layout = Layout {
+            timestamps: true,
+            ..Layout::default()
+        };
+        let (names, input) = random_measurements(&mut rng, layout);
+        let mut config = Config {
+            bucket: Some(*rng.choose(&BucketWidth::ALL)),
+            ..random_engine(&mut rng)
+        };
+        match iter % 3 {
+            0 => listed_stations(&mut rng, &names, &mut config),
+            1 => random_range(&mut rng, &names, &mut config),
+            _ => {}
+        }
+        let width = config.bucket.unwrap();
+        let mut fast = fast_series_results(input.as_bytes(), &config);
+        let summaries = reference::aggregate_series(input.as_bytes(), &config);
+        let mut expected = reference::series_results(&summaries);
+        if let Some(stations) = &config.stations {
+            expected.retain(|series| stations.iter().any(|name| *name == series.result.name));
+        }
+        assert_eq!(
+            format_series(&mut fast, width),
+            format_series(&mut expected, width),
+            "{config:?}\n{input}"
+        );
+        assert_eq!(
+            outliers(&results(&fast)),
+            outliers(&results(&expected)),
+            "{config:?}\n{input}"
+        );
+    }
+}
+
+/// The results of each station and bucket, to compare their outliers.
+fn results<'a>(series: &[series::SeriesResult<'a>]) -> Vec<StationResult<'a>> {
+    series.iter().map(|series| series.result.clone()).collect()
+}
+
+#[test]
+fn groups_match_reference() {
+    use crate::group::{GroupKey, group_results};
+
+    let mut rng = Rng::new(46);
+    let keys = [
+        GroupKey::Separator {
+            separator: '/',
+            n: 1,
+        },
+        GroupKey::Separator {
+            separator: '/',
+            n: 2,
+        },
+        GroupKey::Prefix(2),
+        GroupKey::mapping("a/a/a;x\na/b/a;x\nb/a/a;y\n").unwrap(),
+    ];
+    for _ in 0..if cfg!(miri) { 4 } else { 40 } {
+        // names of 3 levels with few values each, so the groups share their prefixes
+        let part = |rng: &mut Rng| ["a", "b", "cd"][rng.range(0..=2)];
+        let names = (0..rng.range(1..=20))
+            .map(|_| format!("{}/{}/{}", part(&mut rng), part(&mut rng), part(&mut rng)))
+            .collect::<Vec<_>>();
+        let mut input = String::new();
+        for _ in 0..rows(rng.range(0..=2000)) {
+            let value = rng.range(0..=1998) as f64 / 10.0 - 99.9;
+            input.push_str(&format!("{};{value:.1}\n", rng.choose(&names)));
+        }
+        let config = Config::default();
+        let fast = fast_results(input.as_bytes(), &config);
+        let summaries = reference::aggregate(input.as_bytes());
+        let expected = reference::results(&summaries);
+        for key in &keys {
+            let (fast, expected) = (group_results(&fast, key), group_results(&expected, key));
+            let differences = reference::diff(&fast, &expected);
+            assert!(differences.is_empty(), "{key:?}\n{input}");
+        }
+    }
+}
+
+#[test]
+#[cfg_attr(miri, ignore = "several chunks are too large for Miri")]
+fn progress_chunks_match_whole_part() {
+    let input = measurements(rows(1_000_000));
+    let parsed = parsed_part(&input);
+    assert!(parsed.len() > 2 * progress::CHUNK_SIZE);
+
+    let cpu = CpuLevel::detect();
+    let whole = parse_file_bytes::<XorHash, StationSummary>(
+        input.as_bytes(),
+        parsed,
+        ParseOptions::new(cpu, DEFAULT_BATCH),
+        None,
+    );
+    let counter = AtomicUsize::new(0);
+    let chunked = parse_file_bytes::<XorHash, StationSummary>(
+        input.as_bytes(),
+        parsed,
+        ParseOptions::new(cpu, DEFAULT_BATCH),
+        Some(&counter),
+    );
+    assert_eq!(summaries(&chunked), summaries(&whole));
+    assert_eq!(counter.into_inner(), parsed.len());
+}
+
+#[test]
+fn mean_rounds_half_toward_positive_infinity() {
+    for count in 1..=20_u32 {
+        for sum in -200..=200_i64 {
+            let summary = StationSummary {
+                min: 0,
+                max: 0,
+                sum,
+                count,
+                outliers: 0,
+            };
+            let mean = summary.mean_tenths();
+            // mean - 1/2 <= sum / count < mean + 1/2
+            let count = count as i64;
+            assert!(
+                (2 * mean - 1) * count <= 2 * sum && 2 * sum < (2 * mean + 1) * count,
+                "{sum} / {count} rounded to {mean}"
+            );
+        }
+    }
+}
+
+/// The samples in `samples/`, each `.txt` input with its expected output in the `.out` file next to
+/// it: samples of the 1BRC repository as they are (`measurements-*`), and our own edge cases
+/// (`frenzy-*`).
+#[test]
+#[cfg_attr(miri, ignore = "reads the sample files")]
+fn samples_golden_output() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("samples");
+    let mut samples = 0;
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "txt") {
+            continue;
+        }
+        let input = std::fs::read_to_string(&path).unwrap();
+        let expected = std::fs::read_to_string(path.with_extension("out")).unwrap();
+        let expected = expected.trim_end();
+        // repeating the whole input doesn't change the output, and makes the main loop parse most
+        // of it instead of the remainder
+        for repeat in [1, 100] {
+            let input = input.repeat(repeat);
+            for workers in [1, 3] {
+                let config = Config {
+                    workers: Some(workers),
+                    ..Config::default()
+                };
+                let mut results = fast_results(input.as_bytes(), &config);
+                let output = output::format_results(&mut results);
+                assert_eq!(output, expected, "{path:?} x{repeat}, {workers} workers");
+            }
+            let summaries = reference::aggregate(input.as_bytes());
+            let output = output::format_results(&mut reference::results(&summaries));
+            assert_eq!(output, expected, "{path:?} x{repeat}, reference");
+        }
+        samples += 1;
+    }
+    assert!(samples > 0);
+}
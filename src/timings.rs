@@ -0,0 +1,153 @@
+//! Time spent in each phase of a run, and the `--timings` report.
+
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// Wall and CPU time of a phase.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Timing {
+    pub wall: Duration,
+    /// CPU time of the threads running the phase, `None` where it can't be measured
+    pub cpu: Option<Duration>,
+}
+
+/// Measures the wall time, and the CPU time of the current thread, since it was started.
+pub struct Stopwatch {
+    wall: Instant,
+    cpu: Option<Duration>,
+}
+impl Stopwatch {
+    pub fn start() -> Self {
+        Self {
+            wall: Instant::now(),
+            cpu: thread_cpu_time(),
+        }
+    }
+
+    pub fn stop(&self) -> Timing {
+        Timing {
+            wall: self.wall.elapsed(),
+            cpu: thread_cpu_time()
+                .zip(self.cpu)
+                .map(|(end, start)| end - start),
+        }
+    }
+}
+
+/// CPU time consumed by the current thread.
+fn thread_cpu_time() -> Option<Duration> {
+    #[cfg(all(unix, not(miri)))]
+    {
+        let mut time = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        let res = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) };
+        (res == 0).then(|| Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+    }
+    #[cfg(not(all(unix, not(miri))))]
+    {
+        None
+    }
+}
+
+/// The main loop of a single worker.
+#[derive(Clone, Debug, Default)]
+pub struct WorkerTimings {
+    /// Size of the part of the file the worker parsed
+    pub bytes: usize,
+    pub rows: u64,
+    pub parse: Timing,
+}
+
+/// The phases of the fast engine.
+#[derive(Clone, Debug, Default)]
+pub struct EngineTimings {
+    /// Splitting the file and running the main loop of all the workers, the CPU time being the sum
+    /// of the workers
+    pub parse: Timing,
+    pub workers: Vec<WorkerTimings>,
+    /// Merging the hash maps of the workers
+    pub merge: Timing,
+    /// The lines at the end of the file, parsed with bounds checks
+    pub remainder: Timing,
+    pub remainder_bytes: usize,
+    pub remainder_rows: u64,
+    /// Computing the results from the merged hash map
+    pub results: Timing,
+}
+
+/// The phases of a whole run, from mapping the file to the formatted output.
+#[derive(Clone, Debug, Default)]
+pub struct RunTimings {
+    pub mmap: Timing,
+    pub engine: EngineTimings,
+    pub sort: Timing,
+    pub output: Timing,
+    pub total: Timing,
+}
+impl RunTimings {
+    /// The report printed with `--timings`, a line per phase and per worker.
+    ///
+    /// The workers are given parts of the file of about the same size, so a worker much slower than
+    /// the others points at parts with different contents, or at a busy or slower core.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        writeln!(
+            report,
+            "{:<12} {:>10} {:>10} {:>12} {:>12} {:>8}",
+            "phase", "wall ms", "cpu ms", "bytes", "rows", "MB/s"
+        )
+        .unwrap();
+        let mut line = |name: &str, timing: &Timing, bytes_rows: Option<(usize, u64)>| {
+            let cpu = timing
+                .cpu
+                .map_or("-".to_string(), |cpu| format!("{:.2}", millis(cpu)));
+            write!(report, "{name:<12} {:>10.2} {cpu:>10}", millis(timing.wall)).unwrap();
+            if let Some((bytes, rows)) = bytes_rows {
+                let throughput = bytes as f64 / timing.wall.as_secs_f64() / 1e6;
+                write!(report, " {bytes:>12} {rows:>12} {throughput:>8.1}").unwrap();
+            }
+            report.push('\n');
+        };
+
+        let engine = &self.engine;
+        line("mmap", &self.mmap, None);
+        let total_bytes = engine.workers.iter().map(|w| w.bytes).sum();
+        let total_rows = engine.workers.iter().map(|w| w.rows).sum();
+        line("parse", &engine.parse, Some((total_bytes, total_rows)));
+        for (i, worker) in engine.workers.iter().enumerate() {
+            line(
+                &format!("  worker {i}"),
+                &worker.parse,
+                Some((worker.bytes, worker.rows)),
+            );
+        }
+        line("merge", &engine.merge, None);
+        line(
+            "remainder",
+            &engine.remainder,
+            Some((engine.remainder_bytes, engine.remainder_rows)),
+        );
+        line("results", &engine.results, None);
+        line("sort", &self.sort, None);
+        line("output", &self.output, None);
+        line("total", &self.total, None);
+
+        let walls = engine.workers.iter().map(|w| w.parse.wall);
+        if let (Some(fastest), Some(slowest)) = (walls.clone().min(), walls.max()) {
+            write!(
+                report,
+                "slowest worker: {:.2} ms after the fastest ({:.1}% of the parse phase)",
+                millis(slowest - fastest),
+                100.0 * (slowest - fastest).as_secs_f64() / engine.parse.wall.as_secs_f64()
+            )
+            .unwrap();
+        }
+        report
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e3
+}